@@ -1,20 +1,28 @@
+use crate::github::Repository;
+use crate::migration::{self, CONFIG_VERSION};
+use crate::persist;
+use crate::secret::Secret;
 use anyhow::Result;
-use chrono::DateTime;
+use chrono::{DateTime, Utc};
 use core::result::Result::Ok;
-use serde::{Deserialize, Serialize};
+use regex::Regex;
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::{
     collections::HashMap,
-    fs::File,
-    io::{Read, Write},
+    fmt,
+    fs::{self, File},
+    io::Read,
+    path::Path,
 };
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq, Hash)]
 pub enum ScrapeTargetType {
     User,
     Repo,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq)]
 pub struct ScrapeTarget {
     pub username: String,
     #[serde(skip_serializing_if = "Option::is_none", default)]
@@ -22,7 +30,164 @@ pub struct ScrapeTarget {
     pub target_type: ScrapeTargetType,
 
     // ignore scrape target without removing them from config file
+    #[serde(default)]
     pub ignore: bool,
+
+    #[serde(default, skip_serializing_if = "RepoFilter::is_empty")]
+    pub filter: RepoFilter,
+}
+
+/// Narrows down which of a target's repositories are scraped, on top of the
+/// `hacktoberfest` topic. `include` and `exclude` hold globs (`*`, `?`) matched
+/// against the repository name, or regexes when wrapped in slashes (`/^api-/`),
+/// both case-insensitive.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct RepoFilter {
+    // empty includes every repository
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<Pattern>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<Pattern>,
+    #[serde(skip_serializing_if = "is_false")]
+    pub skip_forks: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub skip_archived: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub skip_templates: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_stars: Option<i64>,
+    // days since the last push
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_inactive_days: Option<i64>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// Compiles a `RepoFilter` pattern. Globs are anchored, and both globs and regexes
+/// are case-insensitive like GitHub names.
+pub fn compile_pattern(pattern: &str) -> Result<Regex, regex::Error> {
+    if let Some(regex) = pattern
+        .strip_prefix('/')
+        .and_then(|pattern| pattern.strip_suffix('/'))
+    {
+        return Regex::new(&format!("(?i){regex}"));
+    }
+
+    let mut regex = String::from("(?i)^");
+    for c in pattern.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex)
+}
+
+/// A `RepoFilter` pattern, compiled once when the config is loaded. One that doesn't
+/// compile is kept for `Config::check` to report, and never matches.
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    source: String,
+    regex: Result<Regex, regex::Error>,
+}
+
+impl Pattern {
+    pub fn new(source: impl Into<String>) -> Self {
+        let source = source.into();
+        let regex = compile_pattern(&source);
+        Self { source, regex }
+    }
+
+    pub fn is_match(&self, name: &str) -> bool {
+        self.regex.as_ref().is_ok_and(|regex| regex.is_match(name))
+    }
+
+    pub fn error(&self) -> Option<&regex::Error> {
+        self.regex.as_ref().err()
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Eq for Pattern {}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.source)
+    }
+}
+
+impl Serialize for Pattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.source.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self::new(String::deserialize(deserializer)?))
+    }
+}
+
+impl JsonSchema for Pattern {
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn schema_name() -> String {
+        String::schema_name()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        String::json_schema(gen)
+    }
+}
+
+impl RepoFilter {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Whether `repo` passes the filter.
+    pub fn matches(&self, repo: &Repository, now: DateTime<Utc>) -> bool {
+        let matches_any =
+            |patterns: &[Pattern]| patterns.iter().any(|pattern| pattern.is_match(&repo.name));
+
+        if !self.include.is_empty() && !matches_any(&self.include) {
+            return false;
+        }
+        if matches_any(&self.exclude) {
+            return false;
+        }
+        if (self.skip_forks && repo.fork)
+            || (self.skip_archived && repo.archived)
+            || (self.skip_templates && repo.is_template)
+        {
+            return false;
+        }
+        if self
+            .min_stars
+            .is_some_and(|min_stars| repo.stargazers_count < min_stars)
+        {
+            return false;
+        }
+        if let Some(days) = self.max_inactive_days {
+            let last_active = repo.pushed_at.unwrap_or(repo.updated_at);
+            if now - last_active > chrono::Duration::days(days) {
+                return false;
+            }
+        }
+        true
+    }
 }
 
 impl ScrapeTarget {
@@ -32,6 +197,7 @@ impl ScrapeTarget {
             repo_names: None,
             target_type: ScrapeTargetType::User,
             ignore: false,
+            filter: RepoFilter::default(),
         }
     }
 
@@ -41,6 +207,7 @@ impl ScrapeTarget {
             repo_names: Some(repo_names),
             target_type: ScrapeTargetType::Repo,
             ignore: false,
+            filter: RepoFilter::default(),
         }
     }
 
@@ -49,6 +216,20 @@ impl ScrapeTarget {
         self
     }
 
+    /// Identifies the target in the admin API: the username for User targets,
+    /// `username/repo,...` for Repo targets, lowercase like GitHub compares them.
+    pub fn key(&self) -> String {
+        let key = match (&self.target_type, &self.repo_names) {
+            (ScrapeTargetType::Repo, Some(repo_names)) => {
+                let mut repo_names = repo_names.clone();
+                repo_names.sort();
+                format!("{}/{}", self.username, repo_names.join(","))
+            }
+            _ => self.username.clone(),
+        };
+        key.to_lowercase()
+    }
+
     pub fn target_links(&self) -> Vec<String> {
         self.repo_names
             .as_ref()
@@ -59,35 +240,582 @@ impl ScrapeTarget {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// Thresholds for flagging low-quality pull requests, see `crate::spam`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct SpamPolicy {
+    pub enabled: bool,
+    // flagged pull requests don't count as accepted nor pending
+    pub exclude_flagged: bool,
+    // off at 0, a one-line fix is often a legitimate one
+    pub tiny_diff_max_lines: i64,
+    pub quick_close_minutes: i64,
+    // same author and title across at least this many repositories
+    pub duplicate_min_repos: usize,
+}
+
+impl Default for SpamPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            exclude_flagged: true,
+            tiny_diff_max_lines: 0,
+            quick_close_minutes: 10,
+            duplicate_min_repos: 3,
+        }
+    }
+}
+
+/// Notable events that can be sent to webhooks, see `crate::notifier`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+pub enum NotifyOn {
+    IssueOpened,
+    PullRequestMerged,
+    GoalReached,
+    ScrapeFailing,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq)]
+pub struct WebhookTarget {
+    pub name: String,
+    // webhook URLs usually embed their credentials
+    pub url: Secret,
+    #[serde(default)]
+    pub headers: HashMap<String, Secret>,
+    // empty means every notable event
+    #[serde(default)]
+    pub notify_on: Vec<NotifyOn>,
+    // JSON body with `{{placeholder}}`s, the event itself is sent if unset
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub template: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct NotificationConfig {
+    pub webhooks: Vec<WebhookTarget>,
+    // give up retrying a delivery after this long
+    pub retry_max_elapsed_secs: u64,
+    // failed deliveries are appended here as JSON lines
+    pub dead_letter_path: Option<String>,
+    // consecutive scrape errors before a `ScrapeFailing` notification
+    pub scrape_failure_threshold: u32,
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            webhooks: Vec::new(),
+            retry_max_elapsed_secs: 300,
+            dead_letter_path: None,
+            scrape_failure_threshold: 3,
+        }
+    }
+}
+
+/// Server settings, overridden by `HACKTOBERFEST_*` env vars and CLI flags, see
+/// `crate::settings`. They take effect after a restart.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct ServerSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listen_address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_workers: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scrape_interval: Option<u64>,
+    /// Only `{file: <path>}`, the config file is backed up and often committed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub github_token: Option<Secret>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state_dir: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backup_count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scrape_per_page: Option<u8>,
+}
+
+impl ServerSettings {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// A bearer token for the admin API, `name` is recorded in the audit log.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq)]
+pub struct AdminKey {
+    pub name: String,
+    pub token: Secret,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct AdminConfig {
+    // the admin API is disabled without keys
+    pub keys: Vec<AdminKey>,
+}
+
+/// Cross-origin access for browsers, applied to the public endpoints only. Without
+/// `allowed_origins` no CORS headers are sent.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct CorsConfig {
+    // `https://example.com`, `https://*.example.com` for any subdomain, or `*`
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    // request headers besides the CORS-safelisted ones
+    pub allowed_headers: Vec<String>,
+    // seconds browsers may cache a preflight response
+    pub max_age: Option<usize>,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec![],
+            allowed_methods: vec!["GET".to_owned(), "HEAD".to_owned()],
+            allowed_headers: vec![],
+            max_age: Some(3600),
+        }
+    }
+}
+
+const CORS_METHODS: [&str; 7] = ["GET", "HEAD", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"];
+
+/// `scheme://host[:port]`, the host optionally starting with `*.`.
+fn is_valid_origin_pattern(pattern: &str) -> bool {
+    if pattern == "*" {
+        return true;
+    }
+    let host = match pattern
+        .strip_prefix("https://")
+        .or_else(|| pattern.strip_prefix("http://"))
+    {
+        Some(host) => host,
+        None => return false,
+    };
+    let host = host.strip_prefix("*.").unwrap_or(host);
+    let (name, port) = match host.split_once(':') {
+        Some((name, port)) => (name, Some(port)),
+        None => (host, None),
+    };
+    !name.is_empty()
+        && port.iter().all(|port| port.parse::<u16>().is_ok())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+}
+
+impl CorsConfig {
+    pub fn allows_origin(&self, origin: &str) -> bool {
+        let origin = origin.to_ascii_lowercase();
+        self.allowed_origins.iter().any(|pattern| {
+            let pattern = pattern.to_ascii_lowercase();
+            if pattern == "*" || pattern == origin {
+                return true;
+            }
+            match pattern.split_once("://*.") {
+                Some((scheme, domain)) => origin
+                    .strip_prefix(&format!("{scheme}://"))
+                    .and_then(|host| host.strip_suffix(domain))
+                    .is_some_and(|subdomain| {
+                        subdomain.len() > 1 && subdomain.ends_with('.') && !subdomain.contains('/')
+                    }),
+                None => false,
+            }
+        })
+    }
+}
+
+/// A problem found by `Config::check`, addressed by its path in the config file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Errors make a config unusable, warnings are only logged.
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    pub errors: Vec<ValidationIssue>,
+    pub warnings: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    fn error(&mut self, path: impl Into<String>, message: impl Into<String>) {
+        self.errors.push(ValidationIssue {
+            path: path.into(),
+            message: message.into(),
+        });
+    }
+
+    fn warn(&mut self, path: impl Into<String>, message: impl Into<String>) {
+        self.warnings.push(ValidationIssue {
+            path: path.into(),
+            message: message.into(),
+        });
+    }
+}
+
+impl std::error::Error for ValidationReport {}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid config")?;
+        for issue in self.errors.iter() {
+            write!(f, "\n  {issue}")?;
+        }
+        Ok(())
+    }
+}
+
+fn unversioned() -> u64 {
+    1
+}
+
+/// GitHub logins are 1-39 alphanumerics or single hyphens, not at either end.
+fn is_valid_login(login: &str) -> bool {
+    !login.is_empty()
+        && login.len() <= 39
+        && login.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        && !login.starts_with('-')
+        && !login.ends_with('-')
+        && !login.contains("--")
+}
+
+fn is_valid_repo_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 100
+        && name != "."
+        && name != ".."
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct Config {
+    // see `crate::migration`, files without it are version 1
+    #[serde(default = "unversioned")]
+    pub version: u64,
     pub scrape_target: Vec<ScrapeTarget>,
-    pub scrape_last: Option<DateTime<chrono::prelude::Local>>,
-    pub cached_map: HashMap<String, String>,
+    #[serde(default)]
+    pub spam: SpamPolicy,
+    #[serde(default)]
+    pub notifications: NotificationConfig,
+    #[serde(default)]
+    pub admin: AdminConfig,
+    #[serde(default)]
+    pub cors: CorsConfig,
+    #[serde(default, skip_serializing_if = "ServerSettings::is_empty")]
+    pub server: ServerSettings,
 }
 
 impl Config {
     pub fn default() -> Box<Self> {
         Box::new(Self {
+            version: CONFIG_VERSION,
             scrape_target: vec![ScrapeTarget::user("teknologi-umum".into())],
-            scrape_last: None,
-            cached_map: HashMap::<String, String>::new(),
+            spam: SpamPolicy::default(),
+            notifications: NotificationConfig::default(),
+            admin: AdminConfig::default(),
+            cors: CorsConfig::default(),
+            server: ServerSettings::default(),
         })
     }
 
+    pub fn check(&self) -> ValidationReport {
+        let mut report = ValidationReport::default();
+
+        if self.scrape_target.iter().all(|target| target.ignore) {
+            report.warn("scrape_target", "no target to scrape");
+        }
+        let mut seen = HashMap::<(String, &ScrapeTargetType, Vec<String>), usize>::new();
+        for (i, target) in self.scrape_target.iter().enumerate() {
+            let path = format!("scrape_target[{i}]");
+            if !is_valid_login(&target.username) {
+                report.error(
+                    format!("{path}.username"),
+                    format!("{:?} is not a valid GitHub login", target.username),
+                );
+            }
+
+            match (&target.target_type, &target.repo_names) {
+                (ScrapeTargetType::Repo, None) => {
+                    report.error(format!("{path}.repo_names"), "required for Repo targets")
+                }
+                (ScrapeTargetType::Repo, Some(repo_names)) if repo_names.is_empty() => {
+                    report.error(format!("{path}.repo_names"), "empty, nothing to scrape")
+                }
+                (ScrapeTargetType::User, Some(_)) => report.warn(
+                    format!("{path}.repo_names"),
+                    "ignored for User targets, every repository is scraped",
+                ),
+                _ => {}
+            }
+            let repo_names = target.repo_names.clone().unwrap_or_default();
+            for (j, name) in repo_names.iter().enumerate() {
+                let repo_path = format!("{path}.repo_names[{j}]");
+                if !is_valid_repo_name(name) {
+                    report.error(
+                        repo_path,
+                        format!("{name:?} is not a valid repository name"),
+                    );
+                } else if repo_names[..j].contains(name) {
+                    report.warn(repo_path, format!("{name:?} is listed twice"));
+                }
+            }
+
+            let filter = &target.filter;
+            for (field, patterns) in [("include", &filter.include), ("exclude", &filter.exclude)] {
+                for (j, pattern) in patterns.iter().enumerate() {
+                    if let Some(e) = pattern.error() {
+                        report.error(
+                            format!("{path}.filter.{field}[{j}]"),
+                            format!("{pattern} is not a valid pattern: {e}"),
+                        );
+                    }
+                }
+            }
+            if filter.min_stars.is_some_and(|min_stars| min_stars < 0) {
+                report.error(format!("{path}.filter.min_stars"), "must not be negative");
+            }
+            if filter.max_inactive_days.is_some_and(|days| days < 1) {
+                report.error(
+                    format!("{path}.filter.max_inactive_days"),
+                    "must be at least 1",
+                );
+            }
+
+            // GitHub logins are case-insensitive
+            let mut key_repos = repo_names;
+            key_repos.sort();
+            let key = (
+                target.username.to_lowercase(),
+                &target.target_type,
+                key_repos,
+            );
+            match seen.get(&key) {
+                Some(first) => report.error(path, format!("duplicate of scrape_target[{first}]")),
+                None => {
+                    seen.insert(key, i);
+                }
+            }
+        }
+
+        if self.spam.tiny_diff_max_lines < 0 {
+            report.error("spam.tiny_diff_max_lines", "must not be negative");
+        }
+        if self.spam.quick_close_minutes <= 0 {
+            report.warn(
+                "spam.quick_close_minutes",
+                "no pull request is ever flagged as closed quickly",
+            );
+        }
+        if self.spam.duplicate_min_repos < 2 {
+            report.warn(
+                "spam.duplicate_min_repos",
+                "below 2, every pull request is flagged as a duplicate",
+            );
+        }
+
+        let mut webhook_names = HashMap::<&String, usize>::new();
+        for (i, webhook) in self.notifications.webhooks.iter().enumerate() {
+            let path = format!("notifications.webhooks[{i}]");
+            if webhook.name.is_empty() {
+                report.error(format!("{path}.name"), "must not be empty");
+            } else if let Some(first) = webhook_names.get(&webhook.name) {
+                report.error(
+                    format!("{path}.name"),
+                    format!(
+                        "{:?} is already used by notifications.webhooks[{first}]",
+                        webhook.name
+                    ),
+                );
+            } else {
+                webhook_names.insert(&webhook.name, i);
+            }
+            let url = webhook.url.expose();
+            if !(url.starts_with("https://") || url.starts_with("http://")) {
+                report.error(format!("{path}.url"), "must be an http(s) URL");
+            } else if url.starts_with("http://") {
+                report.warn(format!("{path}.url"), "sent over plain http");
+            }
+        }
+        if self.notifications.scrape_failure_threshold == 0 {
+            report.warn(
+                "notifications.scrape_failure_threshold",
+                "0 never sends a ScrapeFailing notification",
+            );
+        }
+
+        if self.server.num_workers == Some(0) {
+            report.error("server.num_workers", "must be at least 1");
+        }
+        if self.server.scrape_interval == Some(0) {
+            report.error("server.scrape_interval", "must be at least 1");
+        }
+        if self
+            .server
+            .scrape_per_page
+            .is_some_and(|per_page| !(1..=100).contains(&per_page))
+        {
+            report.error("server.scrape_per_page", "must be between 1 and 100");
+        }
+        if self
+            .server
+            .github_token
+            .as_ref()
+            .is_some_and(Secret::is_inline)
+        {
+            report.error(
+                "server.github_token",
+                "must be {file: <path>}, or set it with HACKTOBERFEST_GITHUB_TOKEN_FILE",
+            );
+        }
+
+        let mut key_names = HashMap::<&String, usize>::new();
+        for (i, key) in self.admin.keys.iter().enumerate() {
+            let path = format!("admin.keys[{i}]");
+            if key.name.is_empty() {
+                report.error(format!("{path}.name"), "must not be empty");
+            } else if let Some(first) = key_names.get(&key.name) {
+                report.error(
+                    format!("{path}.name"),
+                    format!("{:?} is already used by admin.keys[{first}]", key.name),
+                );
+            } else {
+                key_names.insert(&key.name, i);
+            }
+            if key.token.expose().len() < 32 {
+                report.error(format!("{path}.token"), "must be at least 32 characters");
+            }
+        }
+
+        for (i, origin) in self.cors.allowed_origins.iter().enumerate() {
+            if !is_valid_origin_pattern(origin) {
+                report.error(
+                    format!("cors.allowed_origins[{i}]"),
+                    format!("{origin:?} is not an origin like https://example.com or https://*.example.com"),
+                );
+            }
+        }
+        for (i, method) in self.cors.allowed_methods.iter().enumerate() {
+            if !CORS_METHODS.contains(&method.as_str()) {
+                report.error(
+                    format!("cors.allowed_methods[{i}]"),
+                    format!("{method:?} is not one of {}", CORS_METHODS.join(", ")),
+                );
+            }
+        }
+        for (i, header) in self.cors.allowed_headers.iter().enumerate() {
+            if actix_web::http::header::HeaderName::from_bytes(header.as_bytes()).is_err() {
+                report.error(
+                    format!("cors.allowed_headers[{i}]"),
+                    format!("{header:?} is not a valid header name"),
+                );
+            }
+        }
+
+        report
+    }
+
+    /// Rejects the config if `check` finds errors, logging its warnings either way.
     pub fn validate(self) -> Result<Self> {
-        Ok(self)
+        let report = self.check();
+        for warning in report.warnings.iter() {
+            log::warn!("config: {warning}");
+        }
+        if report.errors.is_empty() {
+            Ok(self)
+        } else {
+            Err(report.into())
+        }
     }
 
+    /// Loads the config, `None` if there is no file at all.
+    pub fn load(path: &String) -> Result<Option<Box<Self>>> {
+        match Self::from_file(path) {
+            Ok(parsed) => Ok(Some(parsed)),
+            Err(e)
+                if e.downcast_ref::<std::io::Error>()
+                    .is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound) =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Loads the config, creating a default one only if there is no file at all. A
+    /// config that can't be read or parsed is an error, never silently replaced.
     pub fn load_or_create(path: &String) -> Result<Box<Self>> {
-        match Self::from_file(&path) {
-            Ok(parsed) => Ok(parsed),
-            Err(_) => Ok(Self::default().save_yaml_to(&path)?),
+        match Self::load(path) {
+            Ok(Some(parsed)) => Ok(parsed),
+            Ok(None) => Ok(Self::default().save_yaml_to(path)?),
+            Err(e) => Err(e.context(format!("refusing to overwrite config {path}"))),
+        }
+    }
+
+    /// Parses a config file of any supported version, without validating it.
+    pub fn parse(val: &str) -> Result<Self> {
+        let migrated = migration::migrate(serde_yaml::from_str(val)?)?;
+        if migrated.is_migrated() {
+            log::warn!(
+                "config is version {}, migrated to {CONFIG_VERSION} in memory, run `migrate-config` to update the file",
+                migrated.from
+            );
         }
+        Ok(serde_yaml::from_value(migrated.value)?)
     }
 
     pub fn from_yaml(val: &String) -> Result<Self> {
-        Ok(serde_yaml::from_str::<Self>(val)?.validate()?)
+        Self::parse(val)?.validate()
+    }
+
+    /// The target with `key`, see `ScrapeTarget::key`.
+    pub fn target(&self, key: &str) -> Option<&ScrapeTarget> {
+        let key = key.to_lowercase();
+        self.scrape_target.iter().find(|target| target.key() == key)
+    }
+
+    /// Replaces, adds or, given `None`, removes the target with `key`.
+    pub fn set_target(&mut self, key: &str, target: Option<ScrapeTarget>) {
+        let key = key.to_lowercase();
+        let position = self.scrape_target.iter().position(|t| t.key() == key);
+        match (position, target) {
+            (Some(i), Some(target)) => self.scrape_target[i] = target,
+            (Some(i), None) => {
+                self.scrape_target.remove(i);
+            }
+            (None, Some(target)) => self.scrape_target.push(target),
+            (None, None) => {}
+        }
+    }
+
+    /// Puts the targets changed through the admin API over the ones from the file.
+    /// Those changes live in the store, the file is only ever edited by hand.
+    pub fn with_target_overrides(
+        mut self: Box<Self>,
+        overrides: Vec<(String, Option<ScrapeTarget>)>,
+    ) -> Result<Box<Self>> {
+        for (key, target) in overrides {
+            self.set_target(&key, target);
+        }
+        Ok(Box::new(self.validate()?))
+    }
+
+    /// JSON Schema of the config file, for editors.
+    pub fn json_schema() -> Result<String> {
+        Ok(serde_json::to_string_pretty(&schemars::schema_for!(
+            Config
+        ))?)
     }
 
     pub fn from_file(path: &String) -> Result<Box<Self>> {
@@ -109,17 +837,85 @@ impl Config {
     }
 
     pub fn save_yaml_to(self: Box<Self>, path: &String) -> Result<Box<Self>> {
-        let mut handle = File::create(path)?;
-        handle.write_all(self.to_string()?.as_bytes())?;
+        persist::write_atomic(Path::new(path), self.to_string()?.as_bytes())?;
         Ok(self)
     }
 }
 
+/// Runtime state older versions wrote into the config file itself.
+#[derive(Debug, Deserialize, Default)]
+pub struct LegacyState {
+    #[serde(default)]
+    pub scrape_last: Option<DateTime<chrono::prelude::Local>>,
+    #[serde(default)]
+    pub cached_map: HashMap<String, String>,
+}
+
+const LEGACY_STATE_KEYS: [&str; 2] = ["scrape_last", "cached_map"];
+
+/// Drops the top-level `key:` entries and their indented values, leaving comments
+/// and formatting of everything else untouched.
+fn strip_top_level_keys(contents: &str, keys: &[&str]) -> String {
+    let mut stripped = String::with_capacity(contents.len());
+    let mut skipping = false;
+    for line in contents.lines() {
+        let top_level = !line.is_empty() && !line.starts_with(char::is_whitespace);
+        if top_level {
+            skipping = !line.starts_with('#')
+                && keys.iter().any(|key| {
+                    line.strip_prefix(key)
+                        .is_some_and(|rest| rest.starts_with(':'))
+                });
+        }
+        if !skipping {
+            stripped.push_str(line);
+            stripped.push('\n');
+        }
+    }
+    stripped
+}
+
+impl LegacyState {
+    pub fn is_empty(&self) -> bool {
+        self.scrape_last.is_none() && self.cached_map.is_empty()
+    }
+
+    /// Moves runtime state out of a combined config file. The original file is kept
+    /// next to it as `<path>.bak` and the config is rewritten without the state keys.
+    /// Returns `None` if the file has nothing to migrate.
+    pub fn migrate(path: &String) -> Result<Option<Self>> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let raw: serde_yaml::Value = serde_yaml::from_str(&contents)?;
+        let has_state = LEGACY_STATE_KEYS.iter().any(|key| raw.get(key).is_some());
+        if !has_state {
+            return Ok(None);
+        }
+
+        let state: Self = serde_yaml::from_str(&contents)?;
+        persist::write_atomic(Path::new(&format!("{path}.bak")), contents.as_bytes())?;
+        persist::write_atomic(
+            Path::new(path),
+            strip_top_level_keys(&contents, &LEGACY_STATE_KEYS).as_bytes(),
+        )?;
+        Ok(Some(state))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::config::ScrapeTarget;
+    use crate::secret::Secret;
+
+    use std::{env, fs, process};
 
-    use super::Config;
+    use super::{
+        compile_pattern, strip_top_level_keys, Config, CorsConfig, Pattern, RepoFilter,
+        ValidationIssue, LEGACY_STATE_KEYS,
+    };
 
     #[test]
     fn test_scrape_target_ignore() {
@@ -153,9 +949,6 @@ mod tests {
         println!("# CHECK\n{yaml_repr}\n---");
         let conf2 = Config::from_yaml(&yaml_repr)?;
 
-        assert!(conf.cached_map.eq(&conf2.cached_map) == true);
-        assert!(conf.scrape_last == conf2.scrape_last);
-
         assert!(
             conf.scrape_target.len()
                 == conf
@@ -168,4 +961,248 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_strip_legacy_state() -> anyhow::Result<()> {
+        let combined = r#"scrape_target:
+  - username: teknologi-umum
+    target_type: User
+    ignore: false
+  # Add an entry here if you want to add your user or repository
+
+scrape_last: 2023-10-02T00:00:00+07:00
+cached_map:
+  repo: '[]'
+  contributors: '[]'
+# trailing comment
+"#;
+        let stripped = strip_top_level_keys(combined, &LEGACY_STATE_KEYS);
+        assert!(stripped.contains("# Add an entry here"));
+        assert!(stripped.contains("# trailing comment"));
+        assert!(!stripped.contains("scrape_last"));
+        assert!(!stripped.contains("cached_map"));
+        assert!(!stripped.contains("repo: '[]'"));
+
+        let conf = Config::from_yaml(&stripped)?;
+        assert_eq!(conf.scrape_target.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_or_create() -> anyhow::Result<()> {
+        let path = env::temp_dir()
+            .join(format!(
+                "hacktoberfest-{}-load_or_create.yml",
+                process::id()
+            ))
+            .to_string_lossy()
+            .into_owned();
+        let _ = fs::remove_file(&path);
+
+        assert!(Config::load(&path)?.is_none());
+        assert!(fs::metadata(&path).is_err());
+        let created = Config::load_or_create(&path)?;
+        assert_eq!(
+            Config::load_or_create(&path)?.scrape_target,
+            created.scrape_target
+        );
+
+        fs::write(&path, "scrape_target: [")?;
+        assert!(Config::load_or_create(&path).is_err());
+        assert_eq!(fs::read_to_string(&path)?, "scrape_target: [");
+
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_check() {
+        let mut conf = Config::default();
+        conf.scrape_target
+            .push(ScrapeTarget::user("-nobody".to_owned()));
+        conf.scrape_target
+            .push(ScrapeTarget::user("Teknologi-Umum".to_owned()));
+        conf.scrape_target.push(ScrapeTarget {
+            repo_names: None,
+            ..ScrapeTarget::repos("somebody".to_owned(), vec![])
+        });
+        conf.scrape_target.push(ScrapeTarget::repos(
+            "somebody".to_owned(),
+            vec!["a".to_owned(), "a".to_owned(), "../b".to_owned()],
+        ));
+
+        let report = conf.check();
+        let issue = |path: &str, message: &str| ValidationIssue {
+            path: path.to_owned(),
+            message: message.to_owned(),
+        };
+        assert_eq!(
+            report.errors,
+            vec![
+                issue(
+                    "scrape_target[1].username",
+                    "\"-nobody\" is not a valid GitHub login"
+                ),
+                issue("scrape_target[2]", "duplicate of scrape_target[0]"),
+                issue("scrape_target[3].repo_names", "required for Repo targets"),
+                issue(
+                    "scrape_target[4].repo_names[2]",
+                    "\"../b\" is not a valid repository name"
+                ),
+            ]
+        );
+        assert_eq!(
+            report.warnings,
+            vec![issue(
+                "scrape_target[4].repo_names[1]",
+                "\"a\" is listed twice"
+            )]
+        );
+        assert!(conf.validate().is_err());
+        assert!(Config::default().check().errors.is_empty());
+
+        let mut inline = Config::default();
+        inline.server.github_token = Some(Secret::new("ghp_inline"));
+        assert_eq!(
+            inline.check().errors,
+            vec![issue(
+                "server.github_token",
+                "must be {file: <path>}, or set it with HACKTOBERFEST_GITHUB_TOKEN_FILE"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_repo_filter() -> anyhow::Result<()> {
+        let repo = |name: &str, fork: bool, stars: i64| {
+            serde_json::from_value::<crate::github::Repository>(serde_json::json!({
+                "name": name,
+                "full_name": format!("somebody/{name}"),
+                "html_url": format!("https://github.com/somebody/{name}"),
+                "description": null,
+                "language": null,
+                "stargazers_count": stars,
+                "forks_count": 0,
+                "forks": 0,
+                "topics": ["hacktoberfest"],
+                "fork": fork,
+                "created_at": "2022-01-01T00:00:00Z",
+                "updated_at": "2022-09-01T00:00:00Z",
+                "pushed_at": "2022-09-30T00:00:00Z",
+            }))
+            .unwrap()
+        };
+        let now = "2022-10-10T00:00:00Z".parse()?;
+
+        assert!(compile_pattern("api-*")?.is_match("API-server"));
+        assert!(!compile_pattern("api-*")?.is_match("my-api-server"));
+        assert!(compile_pattern("/^(web|api)$/")?.is_match("Web"));
+        assert!(compile_pattern("/(/").is_err());
+
+        let filter = RepoFilter {
+            include: vec![Pattern::new("api-*"), Pattern::new("/^web/")],
+            exclude: vec![Pattern::new("*-legacy")],
+            skip_forks: true,
+            min_stars: Some(10),
+            max_inactive_days: Some(30),
+            ..Default::default()
+        };
+        assert!(filter.matches(&repo("api-server", false, 10), now));
+        assert!(filter.matches(&repo("website", false, 10), now));
+        assert!(!filter.matches(&repo("docs", false, 10), now));
+        assert!(!filter.matches(&repo("api-legacy", false, 10), now));
+        assert!(!filter.matches(&repo("api-server", true, 10), now));
+        assert!(!filter.matches(&repo("api-server", false, 9), now));
+        assert!(!filter.matches(
+            &repo("api-server", false, 10),
+            "2022-12-01T00:00:00Z".parse()?
+        ));
+        assert!(RepoFilter::default().matches(&repo("anything", true, 0), now));
+
+        let mut conf = Config::default();
+        conf.scrape_target[0].filter = RepoFilter {
+            exclude: vec![Pattern::new("/[/")],
+            min_stars: Some(-1),
+            ..Default::default()
+        };
+        let paths: Vec<String> = conf.check().errors.into_iter().map(|e| e.path).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "scrape_target[0].filter.exclude[0]",
+                "scrape_target[0].filter.min_stars"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_cors() {
+        let cors = CorsConfig {
+            allowed_origins: vec![
+                "https://hacktoberfest.teknologiumum.com".to_owned(),
+                "https://*.example.com".to_owned(),
+            ],
+            ..Default::default()
+        };
+        assert!(cors.allows_origin("https://hacktoberfest.teknologiumum.com"));
+        assert!(cors.allows_origin("https://a.b.example.com"));
+        assert!(!cors.allows_origin("https://example.com"));
+        assert!(!cors.allows_origin("https://evilexample.com"));
+        assert!(!cors.allows_origin("http://a.example.com"));
+
+        let mut conf = Config::default();
+        conf.cors = CorsConfig {
+            allowed_origins: vec![
+                "example.com".to_owned(),
+                "https://*.example.com:8443".to_owned(),
+            ],
+            allowed_methods: vec!["GET".to_owned(), "get".to_owned()],
+            ..Default::default()
+        };
+        let paths: Vec<String> = conf.check().errors.into_iter().map(|e| e.path).collect();
+        assert_eq!(
+            paths,
+            vec!["cors.allowed_origins[0]", "cors.allowed_methods[1]"]
+        );
+    }
+
+    #[test]
+    fn test_json_schema_up_to_date() -> anyhow::Result<()> {
+        // regenerate with `hacktoberfest config-schema > config.schema.json`
+        let committed = include_str!("../config.schema.json");
+        assert_eq!(committed.trim_end(), Config::json_schema()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_target_overrides() -> anyhow::Result<()> {
+        let mut config = Box::new(Config::default());
+        config.scrape_target = vec![
+            ScrapeTarget::user("Teknologi-Umum".to_owned()),
+            ScrapeTarget::repos("somebody".to_owned(), vec!["b".to_owned(), "a".to_owned()]),
+        ];
+        assert_eq!(config.scrape_target[1].key(), "somebody/a,b");
+
+        let mut ignored = ScrapeTarget::user("teknologi-umum".to_owned());
+        ignored.ignore = true;
+        let config = config.with_target_overrides(vec![
+            ("teknologi-umum".to_owned(), Some(ignored)),
+            ("somebody/a,b".to_owned(), None),
+            (
+                "newcomer".to_owned(),
+                Some(ScrapeTarget::user("newcomer".to_owned())),
+            ),
+        ])?;
+        assert!(config.target("TEKNOLOGI-UMUM").unwrap().ignore);
+        assert!(config.target("somebody/a,b").is_none());
+        assert_eq!(config.scrape_target.len(), 2);
+
+        let invalid = config.with_target_overrides(vec![(
+            "-invalid".to_owned(),
+            Some(ScrapeTarget::user("-invalid".to_owned())),
+        )]);
+        assert!(invalid.is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,331 @@
+use crate::scraper::{ContributorCollection, PullRequestCollection, RepositoryCollection};
+use crate::PULL_REQUEST_GOAL;
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Only the most recent events are kept in the store.
+pub const MAX_EVENTS: usize = 1000;
+/// Rank changes are only announced for this many places at the top, like the
+/// leaderboard highlights.
+pub const TOP_RANKS: usize = 10;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(tag = "type")]
+pub enum EventKind {
+    IssueOpened {
+        repository: String,
+        html_url: String,
+        title: String,
+    },
+    PullRequestOpened {
+        repository: String,
+        number: i64,
+        html_url: String,
+        title: String,
+        author: String,
+    },
+    PullRequestMerged {
+        repository: String,
+        number: i64,
+        html_url: String,
+        title: String,
+        author: String,
+    },
+    ContributorCountChanged {
+        before: usize,
+        after: usize,
+    },
+    ContributorRankChanged {
+        login: String,
+        before: Option<usize>,
+        after: usize,
+    },
+    GoalReached {
+        login: String,
+        merged_pulls: i64,
+    },
+    ScrapeFailing {
+        attempts: u32,
+        /// What kind of failure, e.g. `github_status`, the details are only logged.
+        error: String,
+    },
+}
+
+impl EventKind {
+    /// The kind and URL of the issue or pull request the event announces, which
+    /// happens once at most.
+    pub fn subject(&self) -> Option<(&'static str, &str)> {
+        match self {
+            Self::IssueOpened { html_url, .. } => Some(("IssueOpened", html_url)),
+            Self::PullRequestOpened { html_url, .. } => Some(("PullRequestOpened", html_url)),
+            Self::PullRequestMerged { html_url, .. } => Some(("PullRequestMerged", html_url)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
+pub struct Event {
+    pub id: u64,
+    pub timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+/// The collections produced by a single scrape.
+pub struct Snapshot {
+    pub repositories: Vec<RepositoryCollection>,
+    pub contributors: Vec<ContributorCollection>,
+    pub pulls: Vec<PullRequestCollection>,
+}
+
+impl Snapshot {
+    /// What `diff` would announce about this snapshot's issues and pull requests,
+    /// as `EventKind::subject`.
+    pub fn subjects(&self) -> Vec<(&'static str, &str)> {
+        let issues = self
+            .repositories
+            .iter()
+            .flat_map(|repo| repo.issues.iter())
+            .map(|issue| ("IssueOpened", issue.html_url.as_str()));
+        let opened = self
+            .pulls
+            .iter()
+            .map(|pull| ("PullRequestOpened", pull.html_url.as_str()));
+        let merged = self
+            .pulls
+            .iter()
+            .filter(|pull| pull.is_merged())
+            .map(|pull| ("PullRequestMerged", pull.html_url.as_str()));
+        issues.chain(opened).chain(merged).collect()
+    }
+}
+
+/// Leaderboard position of each contributor, starting from 1. Ties are broken by
+/// pending pull requests, then by login.
+pub fn rank(contributors: &[ContributorCollection]) -> HashMap<String, usize> {
+    let mut ranked: Vec<&ContributorCollection> = contributors.iter().collect();
+    ranked.sort_by(|a, b| {
+        b.merged_pulls
+            .cmp(&a.merged_pulls)
+            .then(b.pending_pulls.cmp(&a.pending_pulls))
+            .then(a.full_name.cmp(&b.full_name))
+    });
+    ranked
+        .into_iter()
+        .enumerate()
+        .map(|(i, contributor)| (contributor.full_name.clone(), i + 1))
+        .collect()
+}
+
+pub fn diff(previous: &Snapshot, next: &Snapshot) -> Vec<EventKind> {
+    let mut changes = Vec::new();
+
+    let previous_issues: HashSet<&String> = previous
+        .repositories
+        .iter()
+        .flat_map(|repo| repo.issues.iter().map(|issue| &issue.html_url))
+        .collect();
+    for repo in next.repositories.iter() {
+        for issue in repo
+            .issues
+            .iter()
+            .filter(|issue| !previous_issues.contains(&issue.html_url))
+        {
+            changes.push(EventKind::IssueOpened {
+                repository: repo.full_name.clone(),
+                html_url: issue.html_url.clone(),
+                title: issue.title.clone(),
+            });
+        }
+    }
+
+    let previous_pulls: HashMap<&String, &PullRequestCollection> = previous
+        .pulls
+        .iter()
+        .map(|pull| (&pull.html_url, pull))
+        .collect();
+    for pull in next.pulls.iter() {
+        let previous_pull = previous_pulls.get(&pull.html_url);
+        if previous_pull.is_none() {
+            changes.push(EventKind::PullRequestOpened {
+                repository: pull.repository.clone(),
+                number: pull.number,
+                html_url: pull.html_url.clone(),
+                title: pull.title.clone(),
+                author: pull.user.login.clone(),
+            });
+        }
+        if pull.is_merged() && !previous_pull.is_some_and(|p| p.is_merged()) {
+            changes.push(EventKind::PullRequestMerged {
+                repository: pull.repository.clone(),
+                number: pull.number,
+                html_url: pull.html_url.clone(),
+                title: pull.title.clone(),
+                author: pull.user.login.clone(),
+            });
+        }
+    }
+
+    if previous.contributors.len() != next.contributors.len() {
+        changes.push(EventKind::ContributorCountChanged {
+            before: previous.contributors.len(),
+            after: next.contributors.len(),
+        });
+    }
+
+    let previous_merged: HashMap<&String, i64> = previous
+        .contributors
+        .iter()
+        .map(|contributor| (&contributor.full_name, contributor.merged_pulls))
+        .collect();
+    for contributor in next.contributors.iter() {
+        let before = previous_merged
+            .get(&contributor.full_name)
+            .copied()
+            .unwrap_or(0);
+        if before < PULL_REQUEST_GOAL && contributor.merged_pulls >= PULL_REQUEST_GOAL {
+            changes.push(EventKind::GoalReached {
+                login: contributor.full_name.clone(),
+                merged_pulls: contributor.merged_pulls,
+            });
+        }
+    }
+
+    let previous_rank = rank(&previous.contributors);
+    let mut next_rank: Vec<(String, usize)> = rank(&next.contributors).into_iter().collect();
+    next_rank.sort_by_key(|(_, position)| *position);
+    // climbing into or within the top only, anything else would be one event per
+    // contributor whenever somebody overtakes a crowd
+    for (login, after) in next_rank
+        .into_iter()
+        .take_while(|(_, after)| *after <= TOP_RANKS)
+    {
+        let before = previous_rank.get(&login).copied();
+        if before.is_none() || before > Some(after) {
+            changes.push(EventKind::ContributorRankChanged {
+                login,
+                before,
+                after,
+            });
+        }
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scraper::fixtures::{self, contributor};
+    use crate::scraper::{ContributorCollection, PullRequestCollection};
+
+    use super::{diff, rank, EventKind, Snapshot, TOP_RANKS};
+
+    fn pull(number: i64, merged: bool) -> PullRequestCollection {
+        if merged {
+            fixtures::pull("teknologi-umum/pehape", number, "somebody")
+        } else {
+            fixtures::open_pull("teknologi-umum/pehape", number, "somebody")
+        }
+    }
+
+    #[test]
+    fn test_rank() {
+        let ranks = rank(&[
+            contributor("b", 1),
+            contributor("c", 2),
+            contributor("a", 1),
+        ]);
+        assert_eq!(ranks["c"], 1);
+        assert_eq!(ranks["a"], 2);
+        assert_eq!(ranks["b"], 3);
+    }
+
+    #[test]
+    fn test_diff() {
+        let previous = Snapshot {
+            repositories: vec![],
+            contributors: vec![contributor("a", 1)],
+            pulls: vec![pull(1, false)],
+        };
+        let next = Snapshot {
+            repositories: vec![],
+            contributors: vec![contributor("a", 1), contributor("b", 4)],
+            pulls: vec![pull(1, true), pull(2, false)],
+        };
+
+        let changes = diff(&previous, &next);
+        assert!(matches!(
+            &changes[0],
+            EventKind::PullRequestMerged { number: 1, .. }
+        ));
+        assert!(matches!(
+            &changes[1],
+            EventKind::PullRequestOpened { number: 2, .. }
+        ));
+        assert_eq!(
+            changes[2..],
+            [
+                EventKind::ContributorCountChanged {
+                    before: 1,
+                    after: 2
+                },
+                EventKind::GoalReached {
+                    login: "b".to_owned(),
+                    merged_pulls: 4
+                },
+                EventKind::ContributorRankChanged {
+                    login: "b".to_owned(),
+                    before: None,
+                    after: 1
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_rank_changes() {
+        let crowd = || -> Vec<ContributorCollection> {
+            (0..20)
+                .map(|i| contributor(&format!("c{i:02}"), 20 - i))
+                .collect()
+        };
+        let empty = Snapshot {
+            repositories: vec![],
+            contributors: vec![],
+            pulls: vec![],
+        };
+        let previous = Snapshot {
+            repositories: vec![],
+            contributors: crowd(),
+            pulls: vec![],
+        };
+
+        // the first scrape only announces the top
+        let changes = diff(&empty, &previous);
+        let ranked = changes
+            .iter()
+            .filter(|change| matches!(change, EventKind::ContributorRankChanged { .. }))
+            .count();
+        assert_eq!(ranked, TOP_RANKS);
+
+        // c15 overtakes everyone from c02 on, who all move down quietly
+        let mut contributors = crowd();
+        contributors[15].merged_pulls = 20;
+        let next = Snapshot {
+            repositories: vec![],
+            contributors,
+            pulls: vec![],
+        };
+        assert_eq!(
+            diff(&previous, &next),
+            [EventKind::ContributorRankChanged {
+                login: "c15".to_owned(),
+                before: Some(16),
+                after: 2
+            }]
+        );
+    }
+}
@@ -5,6 +5,7 @@ use reqwest::{
     header::{HeaderMap, HeaderValue},
     Client, Response, StatusCode,
 };
+use schemars::JsonSchema;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{collections::HashMap, fmt};
 
@@ -77,11 +78,19 @@ pub struct Repository {
     pub forks_count: i64,
     pub forks: i64,
     pub topics: Vec<String>,
+    #[serde(default)]
+    pub fork: bool,
+    #[serde(default)]
+    pub archived: bool,
+    #[serde(default)]
+    pub is_template: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    #[serde(default)]
+    pub pushed_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 pub struct Issue {
     pub node_id: String,
     pub html_url: String,
@@ -91,16 +100,18 @@ pub struct Issue {
     pub labels: Vec<Label>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub closed_by: Option<User>,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 pub struct User {
     pub login: String,
     pub avatar_url: String,
     pub html_url: String,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 pub struct Label {
     pub name: String,
     pub color: String,
@@ -120,6 +131,7 @@ pub struct PullRequest {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub merged: Option<bool>,
+    pub merged_by: Option<User>,
     pub mergeable_state: Option<String>,
     pub draft: Option<bool>,
     pub requested_reviewers: Option<Vec<User>>,
@@ -131,6 +143,24 @@ pub struct PullRequest {
     pub changed_files: Option<i64>,
 }
 
+#[derive(Deserialize, Serialize)]
+pub struct PullRequestFile {
+    pub filename: String,
+    pub status: String,
+    pub additions: i64,
+    pub deletions: i64,
+    pub changes: i64,
+    /// Missing for binary files and diffs too large to display.
+    pub patch: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct Review {
+    pub user: Option<User>,
+    pub state: String,
+    pub submitted_at: Option<DateTime<Utc>>,
+}
+
 pub struct Github {
     client: Client,
 }
@@ -175,11 +205,22 @@ impl fmt::Display for GithubError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::Request(err) => err.fmt(f),
-            def => write!(f, "{}", def),
+            def => write!(f, "{:?}", def),
         }
     }
 }
 
+/// Scraping only reads public data, which needs no scopes at all. Anything granting
+/// more than reading profile data is more than the token should have.
+pub fn excess_scopes(scopes: &[String]) -> Vec<&str> {
+    const READ_ONLY: [&str; 4] = ["read:user", "user:email", "read:org", "read:discussion"];
+    scopes
+        .iter()
+        .map(String::as_str)
+        .filter(|scope| !READ_ONLY.contains(scope))
+        .collect()
+}
+
 impl Github {
     /// Creates a new Github client with no token (limited to 60 requests/hour).
     /// To increase the limit, provide a token and use `new_with_token` instead.
@@ -202,11 +243,11 @@ impl Github {
                 .expect("failed to set Content-Type header"),
         );
         if let Some(token) = token {
-            headers.insert(
-                http::header::AUTHORIZATION,
-                HeaderValue::from_str(&format!("Bearer {token}")[..])
-                    .expect("failed to set Authorization header"),
-            );
+            let mut authorization = HeaderValue::from_str(&format!("Bearer {token}")[..])
+                .expect("failed to set Authorization header");
+            // keeps the token out of `Debug` output of requests
+            authorization.set_sensitive(true);
+            headers.insert(http::header::AUTHORIZATION, authorization);
         }
 
         let client = Client::builder()
@@ -243,6 +284,35 @@ impl Github {
         Self::wrap_response(response).await
     }
 
+    /// Get the OAuth scopes of the client's token, `None` for fine-grained tokens,
+    /// which don't report them. A rejected token is a `StatusCode(401)` error.
+    ///
+    /// API documentation: https://docs.github.com/en/apps/oauth-apps/building-oauth-apps/scopes-for-oauth-apps
+    pub async fn token_scopes(&self) -> Result<Option<Vec<String>>, GithubError> {
+        let response = self
+            .client
+            .get("https://api.github.com/rate_limit")
+            .send()
+            .await
+            .map_err(GithubError::Request)?;
+        if response.status() != StatusCode::OK {
+            return Err(GithubError::StatusCode(response.status()));
+        }
+
+        Ok(response
+            .headers()
+            .get("x-oauth-scopes")
+            .and_then(|value| value.to_str().ok())
+            .map(|scopes| {
+                scopes
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|scope| !scope.is_empty())
+                    .map(ToOwned::to_owned)
+                    .collect()
+            }))
+    }
+
     /// Lists public repositories for the specified user.
     /// Only shows public repository, sorted by updated, with configurable `per_page` number
     /// of results.
@@ -394,20 +464,101 @@ impl Github {
 
         Self::wrap_response::<PullRequest>(response).await
     }
+
+    /// Lists the files changed by a pull request, with at most 3000 files.
+    ///
+    /// API documentation: https://docs.github.com/en/rest/pulls/pulls#list-pull-requests-files
+    pub async fn list_pull_request_files(
+        &self,
+        user: &String,
+        repo: &String,
+        number: i64,
+    ) -> Result<Vec<PullRequestFile>, GithubError> {
+        let urlencoded_user = urlencoding::encode(&user[..]);
+        let urlencoded_repo = urlencoding::encode(&repo[..]);
+        let request_url = format!(
+            "https://api.github.com/repos/{urlencoded_user}/{urlencoded_repo}/pulls/{number}/files"
+        );
+
+        let response = self
+            .client
+            .get(request_url)
+            .query(&[("per_page", "100")])
+            .send()
+            .await
+            .map_err(GithubError::Request)?;
+
+        Self::wrap_response::<Vec<PullRequestFile>>(response).await
+    }
+
+    /// Gets a single issue by its number. Pull requests are issues too, this is the
+    /// only way to find out who closed one (`closed_by`).
+    ///
+    /// API documentation: https://docs.github.com/en/rest/issues/issues#get-an-issue
+    pub async fn issue(
+        &self,
+        user: &String,
+        repo: &String,
+        number: i64,
+    ) -> Result<Issue, GithubError> {
+        let urlencoded_user = urlencoding::encode(&user[..]);
+        let urlencoded_repo = urlencoding::encode(&repo[..]);
+        let request_url = format!(
+            "https://api.github.com/repos/{urlencoded_user}/{urlencoded_repo}/issues/{number}"
+        );
+
+        let response = self
+            .client
+            .get(request_url)
+            .send()
+            .await
+            .map_err(GithubError::Request)?;
+
+        Self::wrap_response::<Issue>(response).await
+    }
+
+    /// Lists reviews of a pull request in chronological order.
+    ///
+    /// API documentation: https://docs.github.com/en/rest/pulls/reviews#list-reviews-for-a-pull-request
+    pub async fn list_reviews(
+        &self,
+        user: &String,
+        repo: &String,
+        number: i64,
+    ) -> Result<Vec<Review>, GithubError> {
+        let urlencoded_user = urlencoding::encode(&user[..]);
+        let urlencoded_repo = urlencoding::encode(&repo[..]);
+        let request_url = format!(
+            "https://api.github.com/repos/{urlencoded_user}/{urlencoded_repo}/pulls/{number}/reviews"
+        );
+
+        let response = self
+            .client
+            .get(request_url)
+            .query(&[("per_page", "100")])
+            .send()
+            .await
+            .map_err(GithubError::Request)?;
+
+        Self::wrap_response::<Vec<Review>>(response).await
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use chrono::{DateTime, Utc};
 
-    use crate::{github::Github, RunContext};
+    use crate::{
+        github::{excess_scopes, Github},
+        RunContext,
+    };
 
     fn gh_test() -> Github {
         let github_token = RunContext::default().github_token;
         if github_token.is_empty() {
             Github::new()
         } else {
-            Github::new_with_token(Some(String::from(github_token)))
+            Github::new_with_token(Some(github_token.expose().to_owned()))
         }
     }
 
@@ -428,6 +579,13 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_excess_scopes() {
+        let scopes = ["read:user", "repo", "user:email", "admin:org"].map(String::from);
+        assert_eq!(excess_scopes(&scopes), vec!["repo", "admin:org"]);
+        assert!(excess_scopes(&[]).is_empty());
+    }
+
     #[test]
     fn test_chrono_serde() -> Result<(), String> {
         let tcs = vec![r#""2022-09-21T05:52:31Z""#, "null"];
@@ -518,4 +676,37 @@ mod tests {
         assert!(!pull.locked, "should not be locked");
         assert_eq!(pull.state, "closed".to_owned());
     }
+
+    #[tokio::test]
+    async fn test_list_pull_request_files() {
+        let gh = gh_test();
+        let files = gh
+            .list_pull_request_files(&"teknologi-umum".into(), &"pehape".into(), 1)
+            .await
+            .unwrap();
+        assert!(!files.is_empty(), "files len 0");
+    }
+
+    #[tokio::test]
+    async fn test_issue() {
+        let gh = gh_test();
+        let issue = gh
+            .issue(&"teknologi-umum".into(), &"pehape".into(), 1)
+            .await
+            .unwrap();
+        assert!(issue.node_id.starts_with("PR_"), "should be a pull request");
+    }
+
+    #[tokio::test]
+    async fn test_list_reviews() {
+        let gh = gh_test();
+        let reviews = gh
+            .list_reviews(&"teknologi-umum".into(), &"pehape".into(), 1)
+            .await
+            .unwrap();
+        assert!(
+            reviews.iter().all(|review| !review.state.is_empty()),
+            "review without a state"
+        );
+    }
 }
@@ -0,0 +1,417 @@
+use actix_web::{
+    error,
+    http::{header, StatusCode},
+    web::{self, Data},
+    HttpRequest, HttpResponse, Resource, ResponseError, Result,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+use crate::config::{ScrapeTarget, ValidationReport};
+use crate::handlers::error::ApiError;
+use crate::lock::LockRecover;
+use crate::store::{AuditAction, AuditChange, AuditEntry, Store};
+use crate::RunContext;
+
+#[derive(Serialize, Deserialize)]
+pub struct AdminTargetResponse {
+    /// Addresses the target in the other admin routes, see `ScrapeTarget::key`.
+    pub key: String,
+    #[serde(flatten)]
+    pub target: ScrapeTarget,
+}
+
+/// Compares without short-circuiting, so response times don't leak the token.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Name of the admin key the request is authenticated with.
+fn authorize(ctx: &RunContext<'_>, req: &HttpRequest) -> Result<String> {
+    let unauthorized = |reason: &str| {
+        let mut response = ApiError::new(StatusCode::UNAUTHORIZED, reason).error_response();
+        response.headers_mut().insert(
+            header::WWW_AUTHENTICATE,
+            header::HeaderValue::from_static("Bearer"),
+        );
+        error::InternalError::from_response(reason.to_owned(), response).into()
+    };
+
+    let token = match req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    {
+        Some(token) => token,
+        None => return Err(unauthorized("missing bearer token")),
+    };
+
+    // compare against every key, which one matched shouldn't be observable either
+    let mut actor = None;
+    for key in ctx.config.borrow().admin.keys.iter() {
+        if constant_time_eq(key.token.expose().as_bytes(), token.as_bytes()) {
+            actor = Some(key.name.clone());
+        }
+    }
+    actor.ok_or_else(|| unauthorized("invalid bearer token"))
+}
+
+fn invalid(report: &ValidationReport) -> ApiError {
+    let errors: Vec<String> = report.errors.iter().map(ToString::to_string).collect();
+    ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid config")
+        .with_details(serde_json::json!({ "errors": errors }))
+}
+
+fn not_found(key: &str) -> error::Error {
+    ApiError::not_found(format!("scrape_target {key:?} not found")).into()
+}
+
+fn key(req: &HttpRequest) -> String {
+    req.match_info().query("key").to_lowercase()
+}
+
+/// Applies `change` to the target with `key`, which gets the target as it is and
+/// returns it as it should be. The result is kept in the store, on top of the config
+/// file, which is left as its maintainers wrote it, and recorded in the audit log.
+/// The scraper picks it up on its next run.
+async fn apply(
+    ctx: &Data<Mutex<RunContext<'_>>>,
+    req: &HttpRequest,
+    key: &str,
+    change: impl FnOnce(Option<&ScrapeTarget>) -> Result<(AuditAction, Option<ScrapeTarget>)>,
+) -> Result<AuditEntry> {
+    let (actor, mut config, store) = {
+        let g_ctx = ctx.lock_recover();
+        let actor = authorize(&g_ctx, req)?;
+        let config = g_ctx.config.borrow().clone();
+        (actor, config, g_ctx.store.clone())
+    };
+
+    let before = config.target(key).cloned();
+    let (action, after) = change(before.as_ref())?;
+    config.set_target(key, after.clone());
+    let report = config.check();
+    if !report.errors.is_empty() {
+        return Err(invalid(&report).into());
+    }
+
+    let audit = AuditChange {
+        action,
+        key: key.to_owned(),
+        before,
+        after: after.clone(),
+    };
+    let entry = store
+        .blocking(move |store| store.record_target_change(&actor, audit, Utc::now()))
+        .await
+        .map_err(ApiError::internal)?;
+    // onto the running config, not the copy, which misses changes made meanwhile
+    ctx.lock_recover()
+        .config
+        .borrow_mut()
+        .set_target(key, after);
+
+    Ok(entry)
+}
+
+async fn targets(ctx: Data<Mutex<RunContext<'_>>>, req: HttpRequest) -> Result<HttpResponse> {
+    let g_ctx = ctx.lock_recover();
+    authorize(&g_ctx, &req)?;
+
+    let targets: Vec<AdminTargetResponse> = g_ctx
+        .config
+        .borrow()
+        .scrape_target
+        .iter()
+        .cloned()
+        .map(|target| AdminTargetResponse {
+            key: target.key(),
+            target,
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(targets))
+}
+
+async fn add_target(
+    ctx: Data<Mutex<RunContext<'_>>>,
+    req: HttpRequest,
+    target: web::Json<ScrapeTarget>,
+) -> Result<HttpResponse> {
+    let target = target.into_inner();
+    let key = target.key();
+    let entry = apply(&ctx, &req, &key, |before| match before {
+        Some(_) => Err(ApiError::new(
+            StatusCode::CONFLICT,
+            format!("scrape_target {key:?} already exists"),
+        )
+        .into()),
+        None => Ok((AuditAction::Add, Some(target))),
+    })
+    .await?;
+
+    Ok(HttpResponse::Created().json(entry))
+}
+
+async fn update_target(
+    ctx: Data<Mutex<RunContext<'_>>>,
+    req: HttpRequest,
+    target: web::Json<ScrapeTarget>,
+) -> Result<HttpResponse> {
+    let key = key(&req);
+    let target = target.into_inner();
+    if target.key() != key {
+        return Err(ApiError::bad_request(format!(
+            "the target's key would become {:?}, delete it and add it again instead",
+            target.key()
+        ))
+        .into());
+    }
+    let entry = apply(&ctx, &req, &key, |before| match before {
+        Some(_) => Ok((AuditAction::Update, Some(target))),
+        None => Err(not_found(&key)),
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(entry))
+}
+
+async fn delete_target(ctx: Data<Mutex<RunContext<'_>>>, req: HttpRequest) -> Result<HttpResponse> {
+    let key = key(&req);
+    let entry = apply(&ctx, &req, &key, |before| match before {
+        Some(_) => Ok((AuditAction::Delete, None)),
+        None => Err(not_found(&key)),
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(entry))
+}
+
+async fn set_ignore(
+    ctx: Data<Mutex<RunContext<'_>>>,
+    req: HttpRequest,
+    ignore: bool,
+) -> Result<HttpResponse> {
+    let key = key(&req);
+    let entry = apply(&ctx, &req, &key, |before| {
+        let mut target = before.cloned().ok_or_else(|| not_found(&key))?;
+        target.ignore = ignore;
+        let action = if ignore {
+            AuditAction::Ignore
+        } else {
+            AuditAction::Unignore
+        };
+        Ok((action, Some(target)))
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(entry))
+}
+
+async fn ignore_target(ctx: Data<Mutex<RunContext<'_>>>, req: HttpRequest) -> Result<HttpResponse> {
+    set_ignore(ctx, req, true).await
+}
+
+async fn unignore_target(
+    ctx: Data<Mutex<RunContext<'_>>>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    set_ignore(ctx, req, false).await
+}
+
+async fn audit(ctx: Data<Mutex<RunContext<'_>>>, req: HttpRequest) -> Result<HttpResponse> {
+    let store = {
+        let g_ctx = ctx.lock_recover();
+        authorize(&g_ctx, &req)?;
+        g_ctx.store.clone()
+    };
+    let entries = store
+        .blocking(Store::audit_log)
+        .await
+        .map_err(ApiError::internal)?;
+
+    Ok(HttpResponse::Ok().json(entries))
+}
+
+/// Pull requests flagged by the spam heuristics, awaiting review. They name
+/// suspected spammers, so only admins get to see them.
+async fn flagged(ctx: Data<Mutex<RunContext<'_>>>, req: HttpRequest) -> Result<HttpResponse> {
+    let store = {
+        let g_ctx = ctx.lock_recover();
+        authorize(&g_ctx, &req)?;
+        g_ctx.store.clone()
+    };
+    let flagged = store
+        .blocking(Store::flagged_pull_requests)
+        .await
+        .map_err(ApiError::internal)?;
+
+    Ok(HttpResponse::Ok().json(flagged))
+}
+
+pub fn targets_handler() -> Resource {
+    web::resource("/admin/targets")
+        .route(web::get().to(targets))
+        .route(web::post().to(add_target))
+}
+
+pub fn target_handler() -> Resource {
+    web::resource("/admin/targets/{key:.+}")
+        .route(web::put().to(update_target))
+        .route(web::delete().to(delete_target))
+}
+
+pub fn ignore_handler() -> Resource {
+    web::resource("/admin/targets/{key:.+}/ignore").route(web::post().to(ignore_target))
+}
+
+pub fn unignore_handler() -> Resource {
+    web::resource("/admin/targets/{key:.+}/unignore").route(web::post().to(unignore_target))
+}
+
+pub fn audit_handler() -> Resource {
+    web::resource("/admin/audit").route(web::get().to(audit))
+}
+
+pub fn flagged_handler() -> Resource {
+    web::resource("/admin/flagged").route(web::get().to(flagged))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use std::{env, fs, process};
+
+    use actix_web::{http, test::TestRequest, web, web::Data};
+
+    use crate::config::{AdminKey, Config, ScrapeTarget};
+    use crate::secret::Secret;
+    use crate::store::AuditAction;
+    use crate::RunContext;
+
+    use super::{add_target, audit, delete_target, flagged, ignore_target, targets, update_target};
+
+    const TOKEN: &str = "0123456789abcdef0123456789abcdef";
+
+    fn context(name: &str) -> Data<Mutex<RunContext<'static>>> {
+        let mut ctx = RunContext::default();
+        ctx.config_path = env::temp_dir()
+            .join(format!("hacktoberfest-{}-admin-{name}.yml", process::id()))
+            .to_string_lossy()
+            .into_owned();
+        let mut config = Config::default();
+        config.admin.keys.push(AdminKey {
+            name: "somebody".to_owned(),
+            token: Secret::new(TOKEN),
+        });
+        fs::write(&ctx.config_path, config.to_string().unwrap()).unwrap();
+        ctx.config = config.into();
+        Data::new(Mutex::new(ctx))
+    }
+
+    fn authorized() -> TestRequest {
+        TestRequest::default().insert_header(("Authorization", format!("Bearer {TOKEN}")))
+    }
+
+    #[actix_web::test]
+    async fn test_unauthorized() {
+        let ctx = context("unauthorized");
+        let missing = targets(ctx.clone(), TestRequest::default().to_http_request()).await;
+        assert_eq!(
+            missing.unwrap_err().error_response().status(),
+            http::StatusCode::UNAUTHORIZED
+        );
+
+        let req = TestRequest::default()
+            .insert_header(("Authorization", "Bearer wrong"))
+            .to_http_request();
+        let wrong = targets(ctx, req).await;
+        assert_eq!(
+            wrong.unwrap_err().error_response().status(),
+            http::StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_flagged() {
+        let ctx = context("flagged");
+        let anonymous = flagged(ctx.clone(), TestRequest::default().to_http_request()).await;
+        assert_eq!(
+            anonymous.unwrap_err().error_response().status(),
+            http::StatusCode::UNAUTHORIZED
+        );
+
+        let resp = flagged(ctx.clone(), authorized().to_http_request())
+            .await
+            .expect("an error occurred");
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        fs::remove_file(&ctx.lock().unwrap().config_path).unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_manage_targets() {
+        let ctx = context("manage");
+        let config_path = ctx.lock().unwrap().config_path.clone();
+        let written = fs::read_to_string(&config_path).unwrap();
+        let status = |resp: actix_web::Result<actix_web::HttpResponse>| match resp {
+            Ok(resp) => resp.status(),
+            Err(err) => err.error_response().status(),
+        };
+
+        let target = || web::Json(ScrapeTarget::user("somebody".to_owned()));
+        let resp = add_target(ctx.clone(), authorized().to_http_request(), target()).await;
+        assert_eq!(status(resp), http::StatusCode::CREATED);
+        let again = add_target(ctx.clone(), authorized().to_http_request(), target()).await;
+        assert_eq!(status(again), http::StatusCode::CONFLICT);
+
+        let req = authorized().param("key", "SomeBody").to_http_request();
+        ignore_target(ctx.clone(), req)
+            .await
+            .expect("an error occurred");
+        assert!(
+            ctx.lock()
+                .unwrap()
+                .config
+                .borrow()
+                .target("somebody")
+                .unwrap()
+                .ignore
+        );
+
+        // kept apart from the file, which is left alone
+        let store = ctx.lock().unwrap().store.clone();
+        let overrides = store.target_overrides().unwrap();
+        assert_eq!(overrides.len(), 1);
+        assert_eq!(overrides[0].0, "somebody");
+        assert!(overrides[0].1.as_ref().unwrap().ignore);
+        assert_eq!(fs::read_to_string(&config_path).unwrap(), written);
+
+        let req = authorized().param("key", "nobody").to_http_request();
+        let missing = delete_target(ctx.clone(), req).await;
+        assert_eq!(status(missing), http::StatusCode::NOT_FOUND);
+
+        let req = authorized().param("key", "somebody").to_http_request();
+        let renamed = web::Json(ScrapeTarget::user("somebody-else".to_owned()));
+        let resp = update_target(ctx.clone(), req, renamed).await;
+        assert_eq!(status(resp), http::StatusCode::BAD_REQUEST);
+
+        let invalid = web::Json(ScrapeTarget::user("-invalid".to_owned()));
+        let resp = add_target(ctx.clone(), authorized().to_http_request(), invalid).await;
+        assert_eq!(status(resp), http::StatusCode::UNPROCESSABLE_ENTITY);
+
+        let log = store.audit_log().unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0].change.action, AuditAction::Ignore);
+        assert_eq!(log[0].change.key, "somebody");
+        assert_eq!(log[1].actor, "somebody");
+
+        let resp = audit(ctx, authorized().to_http_request())
+            .await
+            .expect("an error occurred");
+        assert_eq!(resp.status(), http::StatusCode::OK);
+
+        fs::remove_file(&config_path).unwrap();
+    }
+}
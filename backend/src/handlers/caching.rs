@@ -0,0 +1,260 @@
+use actix_web::{
+    http::header::{
+        self, CacheControl, CacheDirective, ETag, EntityTag, Header, HttpDate, IfModifiedSince,
+        IfNoneMatch, LastModified,
+    },
+    http::StatusCode,
+    web::Data,
+    HttpRequest, HttpResponse, HttpResponseBuilder, Result,
+};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::handlers::error::ApiError;
+use crate::lock::LockRecover;
+use crate::store::Store;
+use crate::RunContext;
+
+/// Scrapes are minutes apart, a read overlapping more than this many is not worth
+/// waiting for.
+const READ_ATTEMPTS: usize = 3;
+
+/// Cache validators for responses built from scraped data, which only changes when
+/// the next scrape is saved.
+pub struct Freshness {
+    etag: Option<EntityTag>,
+    last_modified: Option<DateTime<Utc>>,
+    // seconds until the next scheduled scrape
+    max_age: i64,
+}
+
+impl Freshness {
+    async fn load(store: &Arc<Store>, scrape_interval: u64) -> Result<Self> {
+        let (etag, last_modified) = store
+            .blocking(Store::version)
+            .await
+            .map_err(ApiError::internal)?;
+        Ok(Self::new(etag, last_modified, scrape_interval, Utc::now()))
+    }
+
+    /// Answers a request for scraped data: a 304 when the client's copy is current,
+    /// otherwise what `respond` makes of the store, with these validators. The
+    /// version is checked again once `respond` is done, a scrape saved meanwhile
+    /// means answering again, so validators and body always match.
+    pub async fn serve<F, Fut>(
+        ctx: &Data<Mutex<RunContext<'_>>>,
+        req: &HttpRequest,
+        respond: F,
+    ) -> Result<HttpResponse>
+    where
+        F: Fn(Arc<Store>, Self) -> Fut,
+        Fut: Future<Output = Result<HttpResponse>>,
+    {
+        let (store, scrape_interval) = {
+            let g_ctx = ctx.lock_recover();
+            (g_ctx.store.clone(), g_ctx.scrape_interval)
+        };
+        for _ in 0..READ_ATTEMPTS {
+            let freshness = Self::load(&store, scrape_interval).await?;
+            if let Some(not_modified) = freshness.not_modified(req) {
+                return Ok(not_modified);
+            }
+            let version = freshness.version().map(ToOwned::to_owned);
+            let response = respond(store.clone(), freshness).await?;
+            let etag = store
+                .blocking(Store::etag)
+                .await
+                .map_err(ApiError::internal)?;
+            if etag == version {
+                return Ok(response);
+            }
+        }
+        Err(ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "the data changed while being read, try again",
+        )
+        .into())
+    }
+
+    pub fn new(
+        etag: Option<String>,
+        last_modified: Option<DateTime<Utc>>,
+        scrape_interval: u64,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let max_age = match last_modified {
+            Some(last_modified) => {
+                let next_scrape = last_modified + Duration::seconds(scrape_interval as i64);
+                (next_scrape - now).num_seconds().max(0)
+            }
+            None => 0,
+        };
+        Self {
+            // weak, compression changes the bytes but not the content
+            etag: etag.map(EntityTag::new_weak),
+            last_modified,
+            max_age,
+        }
+    }
+
+    /// A 304 response if the client's copy is still current. `If-None-Match` takes
+    /// precedence over `If-Modified-Since`, as RFC 9110 requires.
+    pub fn not_modified(&self, req: &HttpRequest) -> Option<HttpResponse> {
+        let fresh = if req.headers().contains_key(header::IF_NONE_MATCH) {
+            match (IfNoneMatch::parse(req), &self.etag) {
+                (Ok(IfNoneMatch::Any), Some(_)) => true,
+                (Ok(IfNoneMatch::Items(tags)), Some(etag)) => {
+                    tags.iter().any(|tag| tag.weak_eq(etag))
+                }
+                _ => false,
+            }
+        } else {
+            match (IfModifiedSince::parse(req), self.last_modified) {
+                (Ok(IfModifiedSince(since)), Some(last_modified)) => {
+                    // HTTP dates have no fraction of a second
+                    let last_modified = Utc.timestamp_opt(last_modified.timestamp(), 0).unwrap();
+                    SystemTime::from(last_modified) <= SystemTime::from(since)
+                }
+                _ => false,
+            }
+        };
+        fresh.then(|| self.headers(HttpResponse::NotModified()).finish())
+    }
+
+    /// Changes with every scrape, unset before the first one.
+    pub fn version(&self) -> Option<&str> {
+        self.etag.as_ref().map(EntityTag::tag)
+    }
+
+    /// A 200 response carrying the validators.
+    pub fn ok(&self) -> HttpResponseBuilder {
+        self.headers(HttpResponse::Ok())
+    }
+
+    fn headers(&self, mut builder: HttpResponseBuilder) -> HttpResponseBuilder {
+        if let Some(etag) = &self.etag {
+            builder.insert_header(ETag(etag.clone()));
+        }
+        match self.last_modified {
+            Some(last_modified) => {
+                builder.insert_header(LastModified(HttpDate::from(SystemTime::from(
+                    last_modified,
+                ))));
+                builder.insert_header(CacheControl(vec![
+                    CacheDirective::Public,
+                    CacheDirective::MaxAge(self.max_age as u32),
+                ]));
+            }
+            // nothing scraped yet, the first scrape may land any moment
+            None => {
+                builder.insert_header(CacheControl(vec![CacheDirective::NoCache]));
+            }
+        }
+        builder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::sync::Mutex;
+
+    use actix_web::{http, test::TestRequest, web::Data, HttpResponse};
+    use chrono::{TimeZone, Utc};
+
+    use crate::events::Snapshot;
+    use crate::RunContext;
+
+    use super::Freshness;
+
+    #[test]
+    fn test_freshness() {
+        let scraped_at = Utc.timestamp_opt(1_696_118_400, 500).unwrap();
+        let now = Utc.timestamp_opt(1_696_118_400 + 600, 0).unwrap();
+        let freshness = Freshness::new(Some("abc".to_owned()), Some(scraped_at), 3600, now);
+
+        let resp = freshness.ok().finish();
+        assert_eq!(resp.headers().get("etag").unwrap(), "W/\"abc\"");
+        assert_eq!(
+            resp.headers().get("cache-control").unwrap(),
+            "public, max-age=3000"
+        );
+        assert_eq!(
+            resp.headers().get("last-modified").unwrap(),
+            "Sun, 01 Oct 2023 00:00:00 GMT"
+        );
+
+        let req = TestRequest::default()
+            .insert_header(("If-None-Match", "\"xyz\", \"abc\""))
+            .to_http_request();
+        let resp = freshness.not_modified(&req).expect("not modified");
+        assert_eq!(resp.status(), http::StatusCode::NOT_MODIFIED);
+
+        let stale = TestRequest::default()
+            .insert_header(("If-None-Match", "\"xyz\""))
+            .insert_header(("If-Modified-Since", "Sun, 01 Oct 2023 00:00:00 GMT"))
+            .to_http_request();
+        assert!(freshness.not_modified(&stale).is_none());
+
+        let since = TestRequest::default()
+            .insert_header(("If-Modified-Since", "Sun, 01 Oct 2023 00:00:00 GMT"))
+            .to_http_request();
+        assert!(freshness.not_modified(&since).is_some());
+        let before = TestRequest::default()
+            .insert_header(("If-Modified-Since", "Sat, 30 Sep 2023 23:59:59 GMT"))
+            .to_http_request();
+        assert!(freshness.not_modified(&before).is_none());
+
+        let unscraped = Freshness::new(None, None, 3600, now);
+        assert_eq!(
+            unscraped
+                .ok()
+                .finish()
+                .headers()
+                .get("cache-control")
+                .unwrap(),
+            "no-cache"
+        );
+        let any = TestRequest::default()
+            .insert_header(("If-None-Match", "*"))
+            .to_http_request();
+        assert!(unscraped.not_modified(&any).is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_serve_rereads_changed_data() {
+        let ctx = Data::new(Mutex::new(RunContext::default()));
+        let req = TestRequest::default().to_http_request();
+        let calls = Cell::new(0);
+
+        let resp = Freshness::serve(&ctx, &req, |store, freshness| {
+            let calls = &calls;
+            async move {
+                calls.set(calls.get() + 1);
+                // a scrape lands while the first answer is being made
+                if calls.get() == 1 {
+                    let snapshot = Snapshot {
+                        repositories: vec![],
+                        contributors: vec![],
+                        pulls: vec![],
+                    };
+                    store
+                        .save_scrape(Utc::now(), &snapshot, &[], vec![])
+                        .unwrap();
+                }
+                Ok::<HttpResponse, actix_web::Error>(freshness.ok().finish())
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(calls.get(), 2);
+        let etag = ctx.lock().unwrap().store.etag().unwrap().unwrap();
+        assert_eq!(
+            resp.headers().get("etag").unwrap().to_str().unwrap(),
+            format!("W/\"{etag}\"")
+        );
+    }
+}
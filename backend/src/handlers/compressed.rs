@@ -0,0 +1,278 @@
+use actix_web::{
+    http::{
+        header::{self, AcceptEncoding, ContentEncoding, Encoding, Header},
+        StatusCode,
+    },
+    web::{self, Bytes},
+    HttpRequest, HttpResponse, ResponseError, Result,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+use crate::handlers::caching::Freshness;
+use crate::handlers::error::ApiError;
+use crate::lock::LockRecover;
+
+/// Preferred first when the client ranks several equally.
+const ENCODINGS: [Encoding; 4] = [
+    Encoding::brotli(),
+    Encoding::zstd(),
+    Encoding::gzip(),
+    Encoding::identity(),
+];
+
+/// Query strings are up to the client, so the number of cached bodies is capped,
+/// the least recently used one makes room for a new one.
+const MAX_ENTRIES: usize = 256;
+
+/// Path and sorted query parameters, so `?a=1&b=2` and `?b=2&a=1` share a body.
+type Key = (String, Vec<(String, String)>, ContentEncoding);
+
+#[derive(Default)]
+struct Entries {
+    // `Freshness` version the bodies were built from
+    version: Option<String>,
+    // bumped on every hit, each body keeps the value of its last one
+    clock: u64,
+    bodies: HashMap<Key, (Bytes, u64)>,
+}
+
+impl Entries {
+    fn get(&mut self, key: &Key) -> Option<Bytes> {
+        self.clock += 1;
+        let (bytes, used) = self.bodies.get_mut(key)?;
+        *used = self.clock;
+        Some(bytes.clone())
+    }
+
+    fn insert(&mut self, key: Key, bytes: Bytes) {
+        if self.bodies.len() >= MAX_ENTRIES && !self.bodies.contains_key(&key) {
+            let oldest = self
+                .bodies
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.bodies.remove(&oldest);
+            }
+        }
+        self.clock += 1;
+        self.bodies.insert(key, (bytes, self.clock));
+    }
+}
+
+/// Encoded response bodies of the large read endpoints. Their data only changes
+/// once per scrape, so each URL is serialized and compressed once per encoding, and
+/// the cache is dropped when the next scrape is saved.
+#[derive(Default)]
+pub struct BodyCache {
+    entries: Mutex<Entries>,
+}
+
+fn encode(encoding: ContentEncoding, json: &[u8]) -> io::Result<Vec<u8>> {
+    match encoding {
+        ContentEncoding::Brotli => {
+            let mut encoded = Vec::new();
+            {
+                let mut writer = brotli::CompressorWriter::new(&mut encoded, 4096, 5, 22);
+                writer.write_all(json)?;
+            }
+            Ok(encoded)
+        }
+        ContentEncoding::Zstd => zstd::encode_all(json, 3),
+        ContentEncoding::Gzip => {
+            let mut writer =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            writer.write_all(json)?;
+            writer.finish()
+        }
+        _ => Ok(json.to_vec()),
+    }
+}
+
+/// The cache key of `req` served with `encoding`. A query string that doesn't parse
+/// is kept whole, the handler rejects it anyway.
+fn key(req: &HttpRequest, encoding: ContentEncoding) -> Key {
+    let mut params = web::Query::<Vec<(String, String)>>::from_query(req.query_string())
+        .map(web::Query::into_inner)
+        .unwrap_or_else(|_| vec![(req.query_string().to_owned(), String::new())]);
+    params.sort();
+    (req.path().to_owned(), params, encoding)
+}
+
+/// The client's preferred encoding among `ENCODINGS`, `None` if it accepts none.
+fn negotiate(req: &HttpRequest) -> Option<ContentEncoding> {
+    let encoding = match AcceptEncoding::parse(req) {
+        Ok(accept) if req.headers().contains_key(header::ACCEPT_ENCODING) => {
+            accept.negotiate(ENCODINGS.iter())?
+        }
+        _ => Encoding::identity(),
+    };
+    match encoding {
+        Encoding::Known(encoding) => Some(encoding),
+        Encoding::Unknown(_) => Some(ContentEncoding::Identity),
+    }
+}
+
+impl BodyCache {
+    /// Responds with `body` as JSON, encoded for the client, from the cache when this
+    /// URL was already served since the last scrape. `body` is awaited on a cache miss
+    /// only, and the compression runs on a blocking thread.
+    pub async fn json<T: Serialize>(
+        &self,
+        req: &HttpRequest,
+        freshness: &Freshness,
+        body: impl Future<Output = Result<T>>,
+    ) -> Result<HttpResponse> {
+        let encoding = match negotiate(req) {
+            Some(encoding) => encoding,
+            None => {
+                let mut response =
+                    ApiError::new(StatusCode::NOT_ACCEPTABLE, "no acceptable content encoding")
+                        .with_details(
+                            serde_json::json!({"supported": ["br", "zstd", "gzip", "identity"]}),
+                        )
+                        .error_response();
+                response.headers_mut().insert(
+                    header::VARY,
+                    header::HeaderValue::from_static("Accept-Encoding"),
+                );
+                return Ok(response);
+            }
+        };
+        let key = key(req, encoding);
+
+        let cached = {
+            let mut entries = self.entries.lock_recover();
+            if entries.version.as_deref() != freshness.version() {
+                entries.version = freshness.version().map(ToOwned::to_owned);
+                entries.bodies.clear();
+            }
+            entries.get(&key)
+        };
+        let bytes = match cached {
+            Some(bytes) => bytes,
+            None => {
+                let json = serde_json::to_vec(&body.await?).map_err(ApiError::internal)?;
+                let encoded = web::block(move || encode(encoding, &json))
+                    .await
+                    .map_err(ApiError::internal)?
+                    .map_err(ApiError::internal)?;
+                let bytes = Bytes::from(encoded);
+                let mut entries = self.entries.lock_recover();
+                // nothing scraped yet, there is no version to invalidate on
+                if entries.version.is_some() && entries.version.as_deref() == freshness.version() {
+                    entries.insert(key, bytes.clone());
+                }
+                bytes
+            }
+        };
+
+        let mut response = freshness.ok();
+        response
+            .content_type(mime::APPLICATION_JSON)
+            .insert_header((header::VARY, "Accept-Encoding"));
+        if encoding != ContentEncoding::Identity {
+            response.insert_header((header::CONTENT_ENCODING, encoding.as_str()));
+        }
+        Ok(response.body(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::io::Read;
+
+    use actix_web::{
+        body,
+        http::{self, header::ContentEncoding},
+        test::TestRequest,
+        web::Bytes,
+    };
+    use chrono::Utc;
+
+    use crate::handlers::caching::Freshness;
+
+    use super::{key, BodyCache, Entries, MAX_ENTRIES};
+
+    #[actix_web::test]
+    async fn test_body_cache() {
+        let cache = BodyCache::default();
+        let freshness = Freshness::new(Some("v1".to_owned()), Some(Utc::now()), 3600, Utc::now());
+        let calls = Cell::new(0);
+        let body = || async {
+            calls.set(calls.get() + 1);
+            Ok(vec!["teknologi-umum/pehape"; 100])
+        };
+        let req = |encoding: &'static str| {
+            TestRequest::default()
+                .uri("/repo?page=1")
+                .insert_header(("Accept-Encoding", encoding))
+                .to_http_request()
+        };
+
+        let resp = cache.json(&req("gzip"), &freshness, body()).await.unwrap();
+        assert_eq!(resp.headers().get("content-encoding").unwrap(), "gzip");
+        let bytes = body::to_bytes(resp.into_body()).await.unwrap();
+        let mut json = String::new();
+        flate2::read::GzDecoder::new(&bytes[..])
+            .read_to_string(&mut json)
+            .unwrap();
+        assert!(json.starts_with("[\"teknologi-umum/pehape\","));
+
+        cache.json(&req("gzip"), &freshness, body()).await.unwrap();
+        assert_eq!(calls.get(), 1);
+        let resp = cache
+            .json(&req("br;q=1, gzip;q=0.5"), &freshness, body())
+            .await
+            .unwrap();
+        assert_eq!(resp.headers().get("content-encoding").unwrap(), "br");
+        let resp = cache
+            .json(&req("identity"), &freshness, body())
+            .await
+            .unwrap();
+        assert!(resp.headers().get("content-encoding").is_none());
+        assert_eq!(calls.get(), 3);
+
+        let next = Freshness::new(Some("v2".to_owned()), Some(Utc::now()), 3600, Utc::now());
+        cache.json(&req("gzip"), &next, body()).await.unwrap();
+        assert_eq!(calls.get(), 4);
+
+        let resp = cache
+            .json(&req("compress, identity;q=0"), &next, body())
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), http::StatusCode::NOT_ACCEPTABLE);
+    }
+
+    #[test]
+    fn test_key() {
+        let key = |uri: &str| {
+            let req = TestRequest::default().uri(uri).to_http_request();
+            key(&req, ContentEncoding::Gzip)
+        };
+        assert_eq!(key("/repo?page=2&q=x"), key("/repo?q=x&page=2"));
+        assert_eq!(key("/repo?q=a%20b"), key("/repo?q=a+b"));
+        assert_ne!(key("/repo?page=2"), key("/pullrequest?page=2"));
+    }
+
+    #[test]
+    fn test_entries_evict_least_recently_used() {
+        let mut entries = Entries::default();
+        let key = |i: usize| (format!("/repo/{i}"), vec![], ContentEncoding::Identity);
+        for i in 0..MAX_ENTRIES {
+            entries.insert(key(i), Bytes::new());
+        }
+        entries.get(&key(0));
+        entries.insert(key(MAX_ENTRIES), Bytes::new());
+
+        assert_eq!(entries.bodies.len(), MAX_ENTRIES);
+        assert!(entries.get(&key(0)).is_some());
+        assert!(entries.get(&key(1)).is_none());
+        assert!(entries.get(&key(MAX_ENTRIES)).is_some());
+    }
+}
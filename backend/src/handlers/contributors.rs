@@ -1,47 +1,115 @@
 use actix_web::{
-    http,
     web::{self, Data},
     HttpRequest, HttpResponse, Resource, Result,
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::sync::Mutex;
 
-use crate::RunContext;
+use crate::handlers::caching::Freshness;
+use crate::handlers::error::ApiError;
+use crate::lock::LockRecover;
+use crate::scraper::{PullRequestCollection, PullRequestEligibility};
+use crate::store::Store;
+use crate::{RunContext, PULL_REQUEST_GOAL};
 
-#[derive(Serialize, Deserialize)]
-pub struct ContributorResponse {
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ContributorPullRequestResponse {
+    #[serde(flatten)]
+    pub pull: PullRequestCollection,
+    pub eligibility: PullRequestEligibility,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ContributorDetailResponse {
     pub full_name: String,
     pub profile_url: String,
+    pub avatar_url: String,
     pub merged_pulls: i64,
     pub pending_pulls: i64,
+    pub goal: i64,
+    pub goal_reached: bool,
+    pub pulls: Vec<ContributorPullRequestResponse>,
 }
 
-async fn contributors(ctx: Data<Mutex<RunContext<'_>>>, _req: HttpRequest) -> Result<HttpResponse> {
-    let unlocked_ctx = ctx.lock().unwrap();
-    let unlocked_map = &unlocked_ctx.config.borrow().cached_map;
-    let cached: String = match unlocked_map.get("contributors") {
-        Some(cached_repo) => cached_repo.into(),
-        _ => "[]".into(),
-    };
-
-    Ok(HttpResponse::Ok()
-        .content_type(http::header::ContentType::json())
-        .body(cached))
+async fn contributors(ctx: Data<Mutex<RunContext<'_>>>, req: HttpRequest) -> Result<HttpResponse> {
+    let body_cache = ctx.lock_recover().body_cache.clone();
+    let (body_cache, req) = (&body_cache, &req);
+
+    Freshness::serve(&ctx, req, |store, freshness| async move {
+        body_cache
+            .json(req, &freshness, async {
+                Ok(store
+                    .blocking(Store::contributors)
+                    .await
+                    .map_err(ApiError::internal)?)
+            })
+            .await
+    })
+    .await
+}
+
+async fn contributor(ctx: Data<Mutex<RunContext<'_>>>, req: HttpRequest) -> Result<HttpResponse> {
+    let login = req.match_info().get("login").unwrap_or_default();
+
+    Freshness::serve(&ctx, &req, |store, freshness| async move {
+        // the counts come from the stored contributor, the same ones `/contrib` lists,
+        // even when some of their pull requests failed to load
+        let author = login.to_owned();
+        let contributor = store
+            .blocking(move |store| store.contributor(&author))
+            .await
+            .map_err(ApiError::internal)?
+            .ok_or_else(|| ApiError::not_found(format!("contributor {login} not found")))?;
+
+        let author = login.to_owned();
+        let pulls: Vec<PullRequestCollection> = store
+            .blocking(move |store| store.pull_requests_by_author(&author))
+            .await
+            .map_err(ApiError::internal)?;
+
+        let response = ContributorDetailResponse {
+            full_name: contributor.full_name,
+            profile_url: contributor.profile_url,
+            avatar_url: contributor.avatar_url,
+            merged_pulls: contributor.merged_pulls,
+            pending_pulls: contributor.pending_pulls,
+            goal: PULL_REQUEST_GOAL,
+            goal_reached: contributor.merged_pulls >= PULL_REQUEST_GOAL,
+            pulls: pulls
+                .into_iter()
+                .map(|pull| ContributorPullRequestResponse {
+                    eligibility: pull.eligibility(),
+                    pull,
+                })
+                .collect(),
+        };
+
+        Ok(freshness.ok().json(response))
+    })
+    .await
 }
 
 pub fn handler() -> Resource {
     web::resource("/contrib").route(web::get().to(contributors))
 }
 
+pub fn detail_handler() -> Resource {
+    web::resource("/contrib/{login}").route(web::get().to(contributor))
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::Mutex;
 
-    use actix_web::{http, test::TestRequest, web::Data};
+    use actix_web::{body, http, test::TestRequest, web::Data};
+    use chrono::Utc;
 
+    use crate::events::Snapshot;
+    use crate::scraper::fixtures;
     use crate::RunContext;
 
-    use super::contributors;
+    use super::{contributor, contributors, ContributorDetailResponse};
 
     #[actix_web::test]
     async fn test_contributors() {
@@ -53,4 +121,59 @@ mod tests {
             http::StatusCode::OK
         );
     }
+
+    #[actix_web::test]
+    async fn test_contributor_not_found() {
+        let ctx = Data::new(Mutex::new(RunContext::default()));
+        let req = TestRequest::default()
+            .param("login", "nobody")
+            .to_http_request();
+        let resp = contributor(ctx, req).await;
+        assert_eq!(
+            resp.unwrap_err().error_response().status(),
+            http::StatusCode::NOT_FOUND
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_contributor() {
+        let ctx = RunContext::default();
+        let snapshot = Snapshot {
+            repositories: vec![],
+            contributors: vec![
+                fixtures::contributor("Somebody", 1),
+                fixtures::contributor("Unloaded", 2),
+            ],
+            pulls: vec![fixtures::pull("teknologi-umum/pehape", 1, "Somebody")],
+        };
+        ctx.store
+            .save_scrape(Utc::now(), &snapshot, &[], vec![])
+            .unwrap();
+        let ctx = Data::new(Mutex::new(ctx));
+        let detail = |login: &'static str| {
+            let ctx = ctx.clone();
+            async move {
+                let req = TestRequest::default()
+                    .param("login", login)
+                    .to_http_request();
+                let resp = contributor(ctx, req).await.expect("an error occurred");
+                assert_eq!(resp.status(), http::StatusCode::OK);
+                let bytes = body::to_bytes(resp.into_body()).await.unwrap();
+                serde_json::from_slice::<ContributorDetailResponse>(&bytes).unwrap()
+            }
+        };
+
+        let somebody = detail("somebody").await;
+        assert_eq!(somebody.full_name, "Somebody");
+        assert_eq!(somebody.merged_pulls, 1);
+        assert_eq!(somebody.pending_pulls, 0);
+        assert_eq!(somebody.pulls.len(), 1);
+        assert!(!somebody.goal_reached);
+
+        // listed by `/contrib` although none of their pull requests loaded
+        let unloaded = detail("unloaded").await;
+        assert_eq!(unloaded.full_name, "Unloaded");
+        assert_eq!(unloaded.merged_pulls, 2);
+        assert!(unloaded.pulls.is_empty());
+    }
 }
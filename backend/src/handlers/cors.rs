@@ -0,0 +1,79 @@
+use actix_cors::Cors;
+use actix_web::http::header;
+
+use crate::config::CorsConfig;
+
+/// CORS middleware for the public endpoints. Built once per worker, so changes to
+/// `cors` take effect after a restart.
+pub fn middleware(config: &CorsConfig) -> Cors {
+    let allowed = config.clone();
+    let mut cors = Cors::default()
+        .allowed_origin_fn(move |origin, _| {
+            origin
+                .to_str()
+                .is_ok_and(|origin| allowed.allows_origin(origin))
+        })
+        .allowed_methods(config.allowed_methods.iter().map(String::as_str))
+        // not CORS-safelisted, but needed to revalidate cached responses
+        .expose_headers([header::ETAG])
+        .max_age(config.max_age);
+    if !config.allowed_headers.is_empty() {
+        cors = cors.allowed_headers(config.allowed_headers.iter().map(String::as_str));
+    }
+    cors
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http, test, web, App, HttpResponse};
+
+    use crate::config::CorsConfig;
+
+    #[actix_web::test]
+    async fn test_cors() {
+        let config = CorsConfig {
+            allowed_origins: vec!["https://*.example.com".to_owned()],
+            ..Default::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .route("/admin", web::get().to(HttpResponse::Ok))
+                .service(
+                    web::scope("")
+                        .wrap(super::middleware(&config))
+                        .route("/repo", web::get().to(HttpResponse::Ok)),
+                ),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/repo")
+            .insert_header(("Origin", "https://www.example.com"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(
+            resp.headers().get("access-control-allow-origin").unwrap(),
+            "https://www.example.com"
+        );
+
+        let req = test::TestRequest::default()
+            .method(http::Method::OPTIONS)
+            .uri("/repo")
+            .insert_header(("Origin", "https://www.example.com"))
+            .insert_header(("Access-Control-Request-Method", "GET"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        assert_eq!(
+            resp.headers().get("access-control-max-age").unwrap(),
+            "3600"
+        );
+
+        let req = test::TestRequest::get()
+            .uri("/admin")
+            .insert_header(("Origin", "https://www.example.com"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.headers().get("access-control-allow-origin").is_none());
+    }
+}
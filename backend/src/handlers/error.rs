@@ -0,0 +1,320 @@
+use std::fmt;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{Service, ServiceRequest, ServiceResponse},
+    error,
+    http::{
+        header::{self, HeaderName, HeaderValue},
+        StatusCode,
+    },
+    web, HttpRequest, HttpResponse, ResponseError,
+};
+use chrono::Utc;
+use futures_util::FutureExt;
+use lazy_static::lazy_static;
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::Value;
+
+pub const REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+tokio::task_local! {
+    // set by `middleware` for the request being handled
+    static CURRENT_REQUEST_ID: String;
+}
+
+lazy_static! {
+    static ref STARTED_AT: i64 = Utc::now().timestamp_millis();
+}
+static REQUEST_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// Body of every error response.
+#[derive(Serialize, JsonSchema)]
+pub struct ErrorBody {
+    /// The status as snake case, e.g. `not_found`.
+    pub code: String,
+    pub message: String,
+    /// Also sent as `X-Request-Id`, quote it when reporting a problem.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+}
+
+/// An error a handler answers with, rendered as an `ErrorBody`.
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    message: String,
+    details: Option<Value>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, message: impl ToString) -> Self {
+        Self {
+            status,
+            message: message.to_string(),
+            details: None,
+        }
+    }
+
+    pub fn bad_request(message: impl ToString) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    pub fn not_found(message: impl ToString) -> Self {
+        Self::new(StatusCode::NOT_FOUND, message)
+    }
+
+    /// Logs `err`, clients only get the request ID to report, not our internals.
+    pub fn internal(err: impl fmt::Display) -> Self {
+        match current_request_id() {
+            Some(id) => log::error!("request {id} failed: {err}"),
+            None => log::error!("request failed: {err}"),
+        }
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, "internal server error")
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    fn code(&self) -> String {
+        self.status
+            .canonical_reason()
+            .unwrap_or("error")
+            .to_lowercase()
+            .replace(' ', "_")
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.code(), self.message)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status).json(ErrorBody {
+            code: self.code(),
+            message: self.message.clone(),
+            request_id: current_request_id(),
+            details: self.details.clone(),
+        })
+    }
+}
+
+fn current_request_id() -> Option<String> {
+    CURRENT_REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// The client's `X-Request-Id` if it's a sane one, so IDs can be followed across
+/// a proxy, otherwise a new one.
+fn request_id(req: &ServiceRequest) -> String {
+    let valid = |id: &&str| {
+        (1..=64).contains(&id.len())
+            && id
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_' || b == b'.')
+    };
+    match req
+        .headers()
+        .get(REQUEST_ID)
+        .and_then(|value| value.to_str().ok())
+        .filter(valid)
+    {
+        Some(id) => id.to_owned(),
+        None => format!(
+            "{:x}-{:x}",
+            *STARTED_AT,
+            REQUEST_COUNT.fetch_add(1, Ordering::Relaxed)
+        ),
+    }
+}
+
+/// Replaces the body of an error response that isn't JSON yet, e.g. the bodiless
+/// 404 and 405 actix answers with itself, and tags the response with `id`.
+fn finish(response: HttpResponse, id: &str) -> HttpResponse {
+    let status = response.status();
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    let mut response = if (status.is_client_error() || status.is_server_error()) && !is_json {
+        let message = status.canonical_reason().unwrap_or("error").to_lowercase();
+        let mut replaced = ApiError::new(status, message).error_response();
+        for (name, value) in response.headers() {
+            if name != header::CONTENT_TYPE
+                && name != header::CONTENT_LENGTH
+                && name != header::CONTENT_ENCODING
+            {
+                replaced.headers_mut().append(name.clone(), value.clone());
+            }
+        }
+        replaced
+    } else {
+        response
+    };
+
+    if let Ok(value) = HeaderValue::from_str(id) {
+        response.headers_mut().insert(REQUEST_ID, value);
+    }
+    response
+}
+
+/// Tags every request with an ID and makes sure whatever goes wrong reaches the
+/// client as an `ErrorBody`, panics included.
+pub fn middleware<S, B>(
+    req: ServiceRequest,
+    srv: &S,
+) -> impl Future<Output = Result<ServiceResponse<BoxBody>, error::Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = error::Error>,
+    B: MessageBody + 'static,
+{
+    let id = request_id(&req);
+    let fut = srv.call(req);
+
+    CURRENT_REQUEST_ID.scope(id.clone(), async move {
+        // the request is gone with an error, the response is rendered here, where
+        // the request ID is known, and handed back as one
+        let err = match AssertUnwindSafe(fut).catch_unwind().await {
+            Ok(Ok(res)) => {
+                let (req, response) = res.map_into_boxed_body().into_parts();
+                return Ok(ServiceResponse::new(req, finish(response, &id)));
+            }
+            Ok(Err(err)) => err,
+            Err(panic) => {
+                let message = panic
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| panic.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                ApiError::internal(format!("handler panicked: {message}")).into()
+            }
+        };
+        let response = finish(err.error_response(), &id);
+        Err(error::InternalError::from_response(err, response).into())
+    })
+}
+
+async fn no_route(req: HttpRequest) -> Result<HttpResponse, ApiError> {
+    Err(ApiError::not_found(format!(
+        "no route for {} {}",
+        req.method(),
+        req.path()
+    )))
+}
+
+/// Answers paths no resource matches.
+pub fn default_service() -> actix_web::Route {
+    web::to(no_route)
+}
+
+/// Turns rejected request bodies, query strings and paths into `ApiError`s.
+pub fn extractor_configs(cfg: &mut web::ServiceConfig) {
+    cfg.app_data(
+        web::JsonConfig::default().error_handler(|err, _| ApiError::bad_request(err).into()),
+    )
+    .app_data(web::QueryConfig::default().error_handler(|err, _| ApiError::bad_request(err).into()))
+    .app_data(web::PathConfig::default().error_handler(|err, _| ApiError::bad_request(err).into()));
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::dev::Service;
+    use actix_web::test::{init_service, TestRequest};
+    use actix_web::{body, http, web, App, HttpResponse};
+    use serde_json::Value;
+
+    use super::{default_service, extractor_configs, middleware, ApiError, REQUEST_ID};
+
+    async fn fails() -> Result<HttpResponse, ApiError> {
+        Err(ApiError::bad_request("page must be at least 1")
+            .with_details(serde_json::json!({"page": 0})))
+    }
+
+    async fn panics() -> HttpResponse {
+        panic!("oops")
+    }
+
+    async fn echo(body: web::Json<Value>) -> HttpResponse {
+        HttpResponse::Ok().json(body.into_inner())
+    }
+
+    #[actix_web::test]
+    async fn test_error_responses() {
+        let app = init_service(
+            App::new()
+                .wrap_fn(middleware)
+                .configure(extractor_configs)
+                .default_service(default_service())
+                .service(web::resource("/fails").route(web::get().to(fails)))
+                .route("/panics", web::get().to(panics))
+                .route("/echo", web::post().to(echo)),
+        )
+        .await;
+        let call = |req: TestRequest| {
+            let app = &app;
+            async move {
+                // errors are rendered by the server, as they would be
+                let resp = match app.call(req.to_request()).await {
+                    Ok(resp) => resp.into_parts().1,
+                    Err(err) => err.error_response(),
+                };
+                let status = resp.status();
+                let id = resp.headers().get(REQUEST_ID).cloned();
+                let bytes = body::to_bytes(resp.into_body()).await.unwrap();
+                let body: Value = serde_json::from_slice(&bytes).unwrap();
+                (status, id, body)
+            }
+        };
+
+        let (status, id, body) = call(TestRequest::get().uri("/fails")).await;
+        assert_eq!(status, http::StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "bad_request");
+        assert_eq!(body["message"], "page must be at least 1");
+        assert_eq!(body["details"]["page"], 0);
+        assert_eq!(body["request_id"], id.unwrap().to_str().unwrap());
+
+        let (status, _, body) = call(
+            TestRequest::get()
+                .uri("/panics")
+                .insert_header((REQUEST_ID, "from-the-proxy")),
+        )
+        .await;
+        assert_eq!(status, http::StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["code"], "internal_server_error");
+        assert_eq!(body["message"], "internal server error");
+        assert_eq!(body["request_id"], "from-the-proxy");
+
+        let (status, _, body) = call(TestRequest::get().uri("/nothing")).await;
+        assert_eq!(status, http::StatusCode::NOT_FOUND);
+        assert_eq!(body["message"], "no route for GET /nothing");
+
+        let (status, _, body) = call(TestRequest::delete().uri("/fails")).await;
+        assert_eq!(status, http::StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(body["code"], "method_not_allowed");
+
+        let (status, _, body) = call(
+            TestRequest::post()
+                .uri("/echo")
+                .insert_header(("Content-Type", "application/json"))
+                .set_payload("{"),
+        )
+        .await;
+        assert_eq!(status, http::StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "bad_request");
+    }
+}
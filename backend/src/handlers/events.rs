@@ -0,0 +1,81 @@
+use actix_web::{
+    web::{self, Data},
+    HttpRequest, HttpResponse, Resource, Result,
+};
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::sync::Mutex;
+
+use crate::events::Event;
+use crate::handlers::error::ApiError;
+use crate::lock::LockRecover;
+use crate::RunContext;
+
+#[derive(Deserialize, JsonSchema)]
+pub struct EventsQuery {
+    /// RFC 3339 timestamp, only events strictly after it are returned.
+    pub since: Option<DateTime<Utc>>,
+}
+
+async fn events(ctx: Data<Mutex<RunContext<'_>>>, req: HttpRequest) -> Result<HttpResponse> {
+    let query =
+        web::Query::<EventsQuery>::from_query(req.query_string()).map_err(ApiError::bad_request)?;
+    let store = ctx.lock_recover().store.clone();
+    let since = query.since;
+    let events: Vec<Event> = store
+        .blocking(move |store| store.events(since))
+        .await
+        .map_err(ApiError::internal)?;
+
+    Ok(HttpResponse::Ok().json(events))
+}
+
+pub fn handler() -> Resource {
+    web::resource("/events").route(web::get().to(events))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use actix_web::{body, http, test::TestRequest, web::Data};
+    use chrono::{TimeZone, Utc};
+
+    use crate::events::{Event, EventKind};
+    use crate::RunContext;
+
+    use super::events;
+
+    #[actix_web::test]
+    async fn test_events() {
+        let ctx = RunContext::default();
+        let change = EventKind::ContributorCountChanged {
+            before: 0,
+            after: 1,
+        };
+        ctx.store
+            .record_events(vec![change.clone()], Utc.timestamp(100, 0))
+            .unwrap();
+        ctx.store
+            .record_events(vec![change], Utc.timestamp(200, 0))
+            .unwrap();
+        let ctx = Data::new(Mutex::new(ctx));
+
+        let req = TestRequest::default()
+            .uri("/events?since=1970-01-01T00:02:00Z")
+            .to_http_request();
+        let resp = events(ctx.clone(), req).await.expect("an error occurred");
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        let bytes = body::to_bytes(resp.into_body()).await.unwrap();
+        let events_since: Vec<Event> = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(events_since.len(), 1);
+        assert_eq!(events_since[0].id, 2);
+
+        let req = TestRequest::default()
+            .uri("/events?since=yesterday")
+            .to_http_request();
+        let resp = events(ctx, req).await;
+        assert!(resp.is_err(), "invalid since should be rejected");
+    }
+}
@@ -0,0 +1,46 @@
+use actix_web::{
+    web::{self, Data},
+    HttpRequest, HttpResponse, Resource, Result,
+};
+use std::sync::Mutex;
+
+use crate::handlers::error::ApiError;
+use crate::lock::LockRecover;
+use crate::store::Store;
+use crate::RunContext;
+
+async fn maintainers(ctx: Data<Mutex<RunContext<'_>>>, _req: HttpRequest) -> Result<HttpResponse> {
+    let store = ctx.lock_recover().store.clone();
+    let maintainers = store
+        .blocking(Store::maintainers)
+        .await
+        .map_err(ApiError::internal)?;
+
+    Ok(HttpResponse::Ok().json(maintainers))
+}
+
+pub fn handler() -> Resource {
+    web::resource("/maintainers").route(web::get().to(maintainers))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use actix_web::{http, test::TestRequest, web::Data};
+
+    use crate::RunContext;
+
+    use super::maintainers;
+
+    #[actix_web::test]
+    async fn test_maintainers() {
+        let ctx = Data::new(Mutex::new(RunContext::default()));
+        let req = TestRequest::default().to_http_request();
+        let resp = maintainers(ctx, req).await;
+        assert_eq!(
+            resp.expect("an error occurred").status(),
+            http::StatusCode::OK
+        );
+    }
+}
@@ -1,8 +1,18 @@
+pub mod admin;
+pub mod caching;
+pub mod compressed;
 pub mod contributors;
+pub mod cors;
+pub mod error;
+pub mod events;
 pub mod healthcheck;
+pub mod maintainers;
 pub mod metrics;
+pub mod openapi;
+pub mod page;
 pub mod pullrequest;
 pub mod repositories;
+pub mod stream;
 
 pub use healthcheck::*;
 pub use metrics::*;
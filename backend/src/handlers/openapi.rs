@@ -0,0 +1,439 @@
+use actix_web::{web, HttpResponse, Resource, Result};
+use lazy_static::lazy_static;
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    schema::{Schema, SchemaObject},
+    JsonSchema,
+};
+use serde_json::{json, Map, Value};
+
+use crate::events::Event;
+use crate::handlers::contributors::ContributorDetailResponse;
+use crate::handlers::error::ErrorBody;
+use crate::handlers::events::EventsQuery;
+use crate::handlers::page::{CursorPage, CursorQuery, Page, PageQuery};
+use crate::handlers::pullrequest::PullRequestQuery;
+use crate::handlers::repositories::RepositoriesQuery;
+use crate::handlers::stream::StreamQuery;
+use crate::handlers::{contributors, events, maintainers, pullrequest, repositories, stream};
+use crate::scraper::{
+    ContributorCollection, MaintainerCollection, PullRequestCollection, RepositoryCollection,
+};
+
+/// Prefix of the current API version, see `OPERATIONS`.
+pub const V1: &str = "/v1";
+
+type SchemaFn = fn(&mut SchemaGenerator) -> Schema;
+
+/// A public endpoint, registered under `V1` and described in the OpenAPI document
+/// from the same entry, so the two can't list different routes.
+pub struct Operation {
+    pub path: &'static str,
+    pub resource: fn() -> Resource,
+    summary: &'static str,
+    query: &'static [SchemaFn],
+    response: SchemaFn,
+    // answers 404 when the path parameters name nothing
+    not_found: bool,
+    // sends validators and answers 304, see `caching::Freshness`
+    cached: bool,
+    // of the 200 response, errors are always JSON
+    media_type: &'static str,
+}
+
+const JSON: &str = "application/json";
+const EVENT_STREAM: &str = "text/event-stream";
+
+fn reference<T: JsonSchema>(gen: &mut SchemaGenerator) -> Schema {
+    gen.subschema_for::<T>()
+}
+
+fn inline<T: JsonSchema>(gen: &mut SchemaGenerator) -> Schema {
+    T::json_schema(gen)
+}
+
+pub const OPERATIONS: [Operation; 9] = [
+    Operation {
+        path: "/repo",
+        resource: repositories::handler,
+        summary: "Repositories taking part, filtered, sorted and paginated",
+        query: &[inline::<RepositoriesQuery>, inline::<PageQuery>],
+        response: reference::<Page<RepositoryCollection>>,
+        not_found: false,
+        cached: true,
+        media_type: JSON,
+    },
+    Operation {
+        path: "/repo/{owner}/{name}",
+        resource: repositories::detail_handler,
+        summary: "One repository with its issues, languages and health",
+        query: &[],
+        response: reference::<RepositoryCollection>,
+        not_found: true,
+        cached: true,
+        media_type: JSON,
+    },
+    Operation {
+        path: "/contrib",
+        resource: contributors::handler,
+        summary: "Contributors with their accepted and pending pull requests",
+        query: &[],
+        response: reference::<Vec<ContributorCollection>>,
+        not_found: false,
+        cached: true,
+        media_type: JSON,
+    },
+    Operation {
+        path: "/contrib/{login}",
+        resource: contributors::detail_handler,
+        summary: "One contributor's progress toward the goal, with their pull requests",
+        query: &[],
+        response: reference::<ContributorDetailResponse>,
+        not_found: true,
+        cached: true,
+        media_type: JSON,
+    },
+    Operation {
+        path: "/maintainers",
+        resource: maintainers::handler,
+        summary: "Maintainers with their reviews and merges",
+        query: &[],
+        response: reference::<Vec<MaintainerCollection>>,
+        not_found: false,
+        cached: false,
+        media_type: JSON,
+    },
+    Operation {
+        path: "/events",
+        resource: events::handler,
+        summary: "Recent changes between scrapes, oldest first",
+        query: &[inline::<EventsQuery>],
+        response: reference::<Vec<Event>>,
+        not_found: false,
+        cached: false,
+        media_type: JSON,
+    },
+    Operation {
+        path: "/pullrequest",
+        resource: pullrequest::handler,
+        summary: "Pull requests, filtered and sorted, walked with `next_cursor`",
+        query: &[inline::<PullRequestQuery>, inline::<CursorQuery>],
+        response: reference::<CursorPage<PullRequestCollection>>,
+        not_found: false,
+        cached: true,
+        media_type: JSON,
+    },
+    Operation {
+        path: "/pullrequest/{owner}/{name}/{number}",
+        resource: pullrequest::detail_handler,
+        summary: "One pull request",
+        query: &[],
+        response: reference::<PullRequestCollection>,
+        not_found: true,
+        cached: true,
+        media_type: JSON,
+    },
+    Operation {
+        path: "/stream",
+        resource: stream::handler,
+        summary: "Events as they are recorded, as Server-Sent Events named after their `type`, \
+                  with a heartbeat comment every 15 seconds. Reconnecting with `Last-Event-ID` \
+                  replays what was missed, or sends a `reset` event when that is no longer \
+                  possible",
+        query: &[inline::<StreamQuery>],
+        response: reference::<Event>,
+        not_found: false,
+        cached: false,
+        media_type: EVENT_STREAM,
+    },
+];
+
+/// Registers every public endpoint, see `OPERATIONS`.
+pub fn services(cfg: &mut web::ServiceConfig) {
+    for operation in OPERATIONS.iter() {
+        cfg.service((operation.resource)());
+    }
+}
+
+/// Applies the OpenAPI fixes of the generator's settings, e.g. `nullable` next to
+/// a `$ref` moves into an `allOf`, where OpenAPI 3.0 doesn't ignore it.
+fn visit(gen: &mut SchemaGenerator, mut schema: Schema) -> Schema {
+    for visitor in gen.visitors_mut() {
+        visitor.visit_schema(&mut schema);
+    }
+    schema
+}
+
+fn parameters(operation: &Operation, gen: &mut SchemaGenerator) -> Vec<Value> {
+    let mut parameters = Vec::new();
+    for segment in operation.path.split('/') {
+        if let Some(name) = segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+            let kind = if name == "number" {
+                "integer"
+            } else {
+                "string"
+            };
+            parameters.push(json!({
+                "name": name,
+                "in": "path",
+                "required": true,
+                "schema": {"type": kind},
+            }));
+        }
+    }
+
+    for query in operation.query.iter() {
+        let schema = query(gen);
+        let schema = SchemaObject::from(visit(gen, schema));
+        let object = match schema.object {
+            Some(object) => object,
+            None => continue,
+        };
+        for (name, property) in object.properties.iter() {
+            let property = serde_json::to_value(property).unwrap_or_default();
+            let mut parameter = json!({
+                "name": name,
+                "in": "query",
+                "required": object.required.contains(name),
+                "schema": property,
+            });
+            if let Some(description) = property.get("description") {
+                parameter["description"] = description.clone();
+            }
+            parameters.push(parameter);
+        }
+    }
+    parameters
+}
+
+fn content(gen: &mut SchemaGenerator, media_type: &str, schema: SchemaFn) -> Value {
+    let schema = schema(gen);
+    json!({ media_type: {"schema": visit(gen, schema)} })
+}
+
+/// The OpenAPI 3 document of `OPERATIONS`.
+pub fn spec() -> Value {
+    let mut gen = SchemaSettings::openapi3().into_generator();
+    let mut paths = Map::new();
+
+    for operation in OPERATIONS.iter() {
+        let mut responses = Map::new();
+        responses.insert(
+            "200".into(),
+            json!({
+                "description": "OK",
+                "content": content(&mut gen, operation.media_type, operation.response),
+            }),
+        );
+        if operation.cached {
+            responses.insert(
+                "304".into(),
+                json!({"description": "Not modified since the `If-None-Match` or `If-Modified-Since` of the request"}),
+            );
+        }
+        let parameters = parameters(operation, &mut gen);
+        let mut error = |status: &str, description: &str| {
+            let content = content(&mut gen, JSON, reference::<ErrorBody>);
+            responses.insert(
+                status.into(),
+                json!({"description": description, "content": content}),
+            );
+        };
+        if !parameters.is_empty() {
+            error("400", "Invalid parameters");
+        }
+        if operation.not_found {
+            error("404", "Not found");
+        }
+        error(
+            "500",
+            "Internal error, `request_id` identifies it in the logs",
+        );
+
+        paths.insert(
+            operation.path.into(),
+            json!({
+                "get": {
+                    "summary": operation.summary,
+                    "parameters": parameters,
+                    "responses": responses,
+                },
+            }),
+        );
+    }
+
+    let mut schemas = gen.take_definitions();
+    for schema in schemas.values_mut() {
+        *schema = visit(&mut gen, schema.clone());
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Teknologi Umum Hacktoberfest",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "servers": [{"url": V1}],
+        "paths": paths,
+        "components": {"schemas": schemas},
+    })
+}
+
+lazy_static! {
+    static ref SPEC: Value = spec();
+}
+
+async fn openapi() -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok().json(&*SPEC))
+}
+
+pub fn handler() -> Resource {
+    web::resource("/openapi.json").route(web::get().to(openapi))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+    use actix_web::web::{self, Data};
+    use actix_web::{http, App};
+    use chrono::Utc;
+    use serde_json::{Map, Value};
+
+    use crate::scraper::fixtures::snapshot;
+    use crate::RunContext;
+
+    use super::{services, spec, V1};
+
+    /// Checks `value` against the subset of JSON Schema schemars generates.
+    fn validate(
+        schema: &Value,
+        value: &Value,
+        schemas: &Map<String, Value>,
+        at: &str,
+    ) -> Result<(), String> {
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            let name = reference.trim_start_matches("#/components/schemas/");
+            let schema = schemas.get(name).ok_or(format!("{at}: no schema {name}"))?;
+            return validate(schema, value, schemas, at);
+        }
+        if value.is_null() && schema.get("nullable") == Some(&Value::Bool(true)) {
+            return Ok(());
+        }
+        if let Some(all_of) = schema.get("allOf").and_then(Value::as_array) {
+            for schema in all_of {
+                validate(schema, value, schemas, at)?;
+            }
+        }
+        for key in ["anyOf", "oneOf"] {
+            if let Some(any_of) = schema.get(key).and_then(Value::as_array) {
+                if !any_of
+                    .iter()
+                    .any(|schema| validate(schema, value, schemas, at).is_ok())
+                {
+                    return Err(format!("{at}: {value} matches no {key}"));
+                }
+            }
+        }
+        if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+            if !options.contains(value) {
+                return Err(format!("{at}: {value} isn't one of {options:?}"));
+            }
+        }
+
+        let kind = match schema.get("type").and_then(Value::as_str) {
+            Some(kind) => kind,
+            None => return Ok(()),
+        };
+        let is_kind = match kind {
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "string" => value.is_string(),
+            "integer" => value.is_i64() || value.is_u64(),
+            "number" => value.is_number(),
+            "boolean" => value.is_boolean(),
+            _ => true,
+        };
+        if !is_kind {
+            return Err(format!("{at}: {value} isn't {kind}"));
+        }
+
+        if let Some(object) = value.as_object() {
+            let required = schema.get("required").and_then(Value::as_array);
+            for name in required.into_iter().flatten().filter_map(Value::as_str) {
+                if !object.contains_key(name) {
+                    return Err(format!("{at}: missing {name}"));
+                }
+            }
+            if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+                for (name, field) in object.iter() {
+                    let property = properties
+                        .get(name)
+                        .ok_or(format!("{at}: undocumented {name}"))?;
+                    validate(property, field, schemas, &format!("{at}.{name}"))?;
+                }
+            }
+        }
+        if let (Some(items), Some(schema)) = (value.as_array(), schema.get("items")) {
+            for (i, item) in items.iter().enumerate() {
+                validate(schema, item, schemas, &format!("{at}[{i}]"))?;
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_openapi_json() {
+        let committed: Value = serde_json::from_str(include_str!("../../openapi.json")).unwrap();
+        assert!(
+            committed == spec(),
+            "openapi.json is out of date, run `hacktoberfest openapi > openapi.json`"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_spec_matches_handlers() {
+        let ctx = RunContext::default();
+        ctx.store
+            .save_scrape(Utc::now(), &snapshot(), &[], vec![])
+            .unwrap();
+        let app = init_service(
+            App::new()
+                .app_data(Data::new(Mutex::new(ctx)))
+                .service(web::scope(V1).configure(services)),
+        )
+        .await;
+
+        let spec = spec();
+        let schemas = spec["components"]["schemas"].as_object().unwrap();
+        for (path, item) in spec["paths"].as_object().unwrap() {
+            let uri = path
+                .replace("{owner}", "teknologi-umum")
+                .replace("{name}", "pehape")
+                .replace("{number}", "1")
+                .replace("{login}", "somebody");
+            let req = TestRequest::get().uri(&format!("{V1}{uri}")).to_request();
+            let resp = call_service(&app, req).await;
+            assert_eq!(resp.status(), http::StatusCode::OK, "{path}");
+            let content = item["get"]["responses"]["200"]["content"]
+                .as_object()
+                .unwrap();
+            if !content.contains_key("application/json") {
+                // a stream, its body never ends
+                let content_type = resp.headers().get("Content-Type").unwrap();
+                assert!(
+                    content.contains_key(content_type.to_str().unwrap()),
+                    "{path}"
+                );
+                continue;
+            }
+
+            let body: Value = read_body_json(resp).await;
+            let schema = &item["get"]["responses"]["200"]["content"]["application/json"]["schema"];
+            if let Err(e) = validate(schema, &body, schemas, path) {
+                panic!("{e}");
+            }
+        }
+    }
+}
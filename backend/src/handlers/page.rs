@@ -0,0 +1,173 @@
+use actix_web::{error, web, HttpRequest, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::handlers::error::ApiError;
+
+const DEFAULT_PER_PAGE: usize = 30;
+const MAX_PER_PAGE: usize = 100;
+/// Far past the end of any list, and small enough that the offset can't overflow.
+const MAX_PAGE: usize = 1_000_000;
+
+/// `page` and `per_page` query parameters, `page` starts at 1.
+#[derive(Deserialize, JsonSchema)]
+pub struct PageQuery {
+    #[serde(default = "first_page")]
+    pub page: usize,
+    #[serde(default = "default_per_page")]
+    pub per_page: usize,
+}
+
+fn first_page() -> usize {
+    1
+}
+
+fn default_per_page() -> usize {
+    DEFAULT_PER_PAGE
+}
+
+impl PageQuery {
+    /// Parsed on its own, `serde(flatten)` loses the types of query parameters.
+    pub fn from_request(req: &HttpRequest) -> Result<Self> {
+        let query = web::Query::<Self>::from_query(req.query_string())
+            .map_err(ApiError::bad_request)?
+            .into_inner();
+        if !(1..=MAX_PAGE).contains(&query.page) {
+            return Err(
+                ApiError::bad_request(format!("page must be between 1 and {MAX_PAGE}")).into(),
+            );
+        }
+        if !(1..=MAX_PER_PAGE).contains(&query.per_page) {
+            return Err(ApiError::bad_request(format!(
+                "per_page must be between 1 and {MAX_PER_PAGE}"
+            ))
+            .into());
+        }
+        Ok(query)
+    }
+}
+
+/// One page of a list, with the size of the whole list.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: usize,
+    pub page: usize,
+    pub per_page: usize,
+}
+
+impl<T> Page<T> {
+    /// Pages past the end are empty.
+    pub fn paginate(items: Vec<T>, query: &PageQuery) -> Self {
+        let total = items.len();
+        let items = items
+            .into_iter()
+            .skip((query.page - 1) * query.per_page)
+            .take(query.per_page)
+            .collect();
+        Self {
+            items,
+            total,
+            page: query.page,
+            per_page: query.per_page,
+        }
+    }
+}
+
+/// `cursor` and `limit` query parameters, for lists clients walk with `next_cursor`.
+#[derive(Deserialize, JsonSchema)]
+pub struct CursorQuery {
+    pub cursor: Option<String>,
+    #[serde(default = "default_per_page")]
+    pub limit: usize,
+}
+
+impl CursorQuery {
+    pub fn from_request(req: &HttpRequest) -> Result<Self> {
+        let query = web::Query::<Self>::from_query(req.query_string())
+            .map_err(ApiError::bad_request)?
+            .into_inner();
+        if !(1..=MAX_PER_PAGE).contains(&query.limit) {
+            return Err(ApiError::bad_request(format!(
+                "limit must be between 1 and {MAX_PER_PAGE}"
+            ))
+            .into());
+        }
+        Ok(query)
+    }
+
+    /// The position encoded in `cursor`, see `encode_cursor`.
+    pub fn position(&self) -> Result<Option<String>> {
+        let cursor = match &self.cursor {
+            Some(cursor) => cursor,
+            None => return Ok(None),
+        };
+        let invalid = || error::Error::from(ApiError::bad_request("invalid cursor"));
+        if cursor.len() % 2 != 0 {
+            return Err(invalid());
+        }
+        let bytes = (0..cursor.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(cursor.get(i..i + 2)?, 16).ok())
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(invalid)?;
+        String::from_utf8(bytes).map(Some).map_err(|_| invalid())
+    }
+}
+
+/// Cursors are opaque to clients, so the position format can change.
+pub fn encode_cursor(position: &str) -> String {
+    position.bytes().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Part of a list, `next_cursor` is unset on the last part.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct CursorPage<T> {
+    pub items: Vec<T>,
+    pub total: usize,
+    pub next_cursor: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+
+    use super::{encode_cursor, CursorQuery, Page, PageQuery};
+
+    #[test]
+    fn test_paginate() {
+        let req = TestRequest::default()
+            .uri("/repo?page=2&per_page=2")
+            .to_http_request();
+        let query = PageQuery::from_request(&req).unwrap();
+        let page = Page::paginate(vec![1, 2, 3], &query);
+        assert_eq!(page.items, vec![3]);
+        assert_eq!(page.total, 3);
+
+        for uri in [
+            "/repo?page=0",
+            "/repo?per_page=101",
+            "/repo?page=first",
+            "/repo?page=184467440737095517",
+        ] {
+            let req = TestRequest::default().uri(uri).to_http_request();
+            assert!(PageQuery::from_request(&req).is_err(), "{uri}");
+        }
+    }
+
+    #[test]
+    fn test_cursor() {
+        let uri = format!("/pullrequest?cursor={}", encode_cursor("1:a/b#2"));
+        let req = TestRequest::default().uri(&uri).to_http_request();
+        let query = CursorQuery::from_request(&req).unwrap();
+        assert_eq!(query.position().unwrap().as_deref(), Some("1:a/b#2"));
+        assert_eq!(query.limit, 30);
+
+        for cursor in ["abc", "zz", "ff"] {
+            let uri = format!("/pullrequest?cursor={cursor}");
+            let req = TestRequest::default().uri(&uri).to_http_request();
+            let query = CursorQuery::from_request(&req).unwrap();
+            assert!(query.position().is_err(), "{cursor}");
+        }
+    }
+}
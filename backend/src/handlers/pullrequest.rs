@@ -1,34 +1,375 @@
+use crate::handlers::caching::Freshness;
+use crate::handlers::error::ApiError;
+use crate::handlers::page::{encode_cursor, CursorPage, CursorQuery};
+use crate::lock::LockRecover;
+use crate::scraper::{PullRequestCollection, PullRequestEligibility, PullRequestState};
+use crate::store::Store;
 use crate::RunContext;
 use actix_web::web::Data;
-use actix_web::{http, web, HttpRequest, HttpResponse, Resource, Result};
+use actix_web::{web, HttpRequest, HttpResponse, Resource, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use schemars::JsonSchema;
+use serde::Deserialize;
 use std::sync::Mutex;
 
-async fn pullrequest(ctx: Data<Mutex<RunContext<'_>>>, _req: HttpRequest) -> Result<HttpResponse> {
-    let unlocked_ctx = ctx.lock().unwrap();
-    let unlocked_map = &unlocked_ctx.config.borrow().cached_map;
-    let cached: String = match unlocked_map.get("pull_request") {
-        Some(cached_pr) => cached_pr.into(),
-        None => "[]".into(),
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq, Debug, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PullRequestSort {
+    #[default]
+    CreatedAt,
+    MergedAt,
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq, Debug, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// `state` and `eligibility` are spelled as in the response, e.g. `Open`, `Accepted`.
+#[derive(Deserialize, Default, JsonSchema)]
+pub struct PullRequestQuery {
+    /// `owner/name`, case-insensitive.
+    pub repo: Option<String>,
+    /// Login of the author, case-insensitive.
+    pub author: Option<String>,
+    pub state: Option<PullRequestState>,
+    pub merged: Option<bool>,
+    pub eligibility: Option<PullRequestEligibility>,
+    /// RFC 3339 timestamps, bounds of `created_at`, inclusive.
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub sort: PullRequestSort,
+    #[serde(default)]
+    pub order: SortOrder,
+}
+
+/// Sort key, unique per pull request so pages never overlap.
+type Position = (DateTime<Utc>, String, i64);
+
+impl PullRequestQuery {
+    fn matches(&self, pull: &PullRequestCollection) -> bool {
+        if let Some(repo) = &self.repo {
+            if !repo.eq_ignore_ascii_case(&pull.repository) {
+                return false;
+            }
+        }
+        if let Some(author) = &self.author {
+            if !author.eq_ignore_ascii_case(&pull.user.login) {
+                return false;
+            }
+        }
+        if let Some(state) = &self.state {
+            if *state != pull.state {
+                return false;
+            }
+        }
+        if let Some(merged) = self.merged {
+            if merged != pull.is_merged() {
+                return false;
+            }
+        }
+        if let Some(eligibility) = &self.eligibility {
+            if *eligibility != pull.eligibility() {
+                return false;
+            }
+        }
+        self.since.iter().all(|since| pull.created_at >= *since)
+            && self.until.iter().all(|until| pull.created_at <= *until)
+    }
+
+    fn position(&self, pull: &PullRequestCollection) -> Position {
+        let key = match self.sort {
+            PullRequestSort::CreatedAt => pull.created_at,
+            PullRequestSort::MergedAt => pull.merged_at,
+        };
+        (key, pull.repository.clone(), pull.number)
+    }
+}
+
+fn encode_position((key, repository, number): &Position) -> String {
+    encode_cursor(&format!("{}:{number}:{repository}", key.timestamp_millis()))
+}
+
+fn decode_position(position: &str) -> Option<Position> {
+    let mut parts = position.splitn(3, ':');
+    let key = Utc
+        .timestamp_millis_opt(parts.next()?.parse().ok()?)
+        .single()?;
+    let number = parts.next()?.parse().ok()?;
+    Some((key, parts.next()?.to_owned(), number))
+}
+
+/// Filters and sorts `pulls`, then takes up to `limit` of them after `cursor`.
+pub fn query_pull_requests(
+    pulls: Vec<PullRequestCollection>,
+    query: &PullRequestQuery,
+    cursor: &CursorQuery,
+) -> Result<CursorPage<PullRequestCollection>> {
+    let after = match cursor.position()? {
+        Some(position) => Some(
+            decode_position(&position).ok_or_else(|| ApiError::bad_request("invalid cursor"))?,
+        ),
+        None => None,
     };
 
-    Ok(HttpResponse::Ok()
-        .content_type(http::header::ContentType::json())
-        .body(cached))
+    let mut pulls: Vec<(Position, PullRequestCollection)> = pulls
+        .into_iter()
+        .filter(|pull| query.matches(pull))
+        .map(|pull| (query.position(&pull), pull))
+        .collect();
+    pulls.sort_by(|(a, _), (b, _)| a.cmp(b));
+    if query.order == SortOrder::Desc {
+        pulls.reverse();
+    }
+    let total = pulls.len();
+
+    let is_after = |position: &Position| match (&after, query.order) {
+        (None, _) => true,
+        (Some(after), SortOrder::Asc) => position > after,
+        (Some(after), SortOrder::Desc) => position < after,
+    };
+    let mut page: Vec<(Position, PullRequestCollection)> = pulls
+        .into_iter()
+        .filter(|(position, _)| is_after(position))
+        .take(cursor.limit + 1)
+        .collect();
+    let next_cursor = if page.len() > cursor.limit {
+        page.truncate(cursor.limit);
+        page.last().map(|(position, _)| encode_position(position))
+    } else {
+        None
+    };
+
+    Ok(CursorPage {
+        items: page.into_iter().map(|(_, pull)| pull).collect(),
+        total,
+        next_cursor,
+    })
+}
+
+async fn pullrequest(ctx: Data<Mutex<RunContext<'_>>>, req: HttpRequest) -> Result<HttpResponse> {
+    let query = web::Query::<PullRequestQuery>::from_query(req.query_string())
+        .map_err(ApiError::bad_request)?;
+    let cursor = CursorQuery::from_request(&req)?;
+    let body_cache = ctx.lock_recover().body_cache.clone();
+    let (body_cache, req, query, cursor) = (&body_cache, &req, &query, &cursor);
+
+    Freshness::serve(&ctx, req, |store, freshness| async move {
+        body_cache
+            .json(req, &freshness, async {
+                let pulls = store
+                    .blocking(Store::pull_requests)
+                    .await
+                    .map_err(ApiError::internal)?;
+                query_pull_requests(pulls, query, cursor)
+            })
+            .await
+    })
+    .await
+}
+
+async fn pullrequest_detail(
+    ctx: Data<Mutex<RunContext<'_>>>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    let repository = format!(
+        "{}/{}",
+        req.match_info().query("owner"),
+        req.match_info().query("name")
+    );
+    let number: i64 = req
+        .match_info()
+        .query("number")
+        .parse()
+        .map_err(ApiError::bad_request)?;
+    let repository = &repository;
+
+    Freshness::serve(&ctx, &req, |store, freshness| async move {
+        let name = repository.clone();
+        let pull = store
+            .blocking(move |store| store.pull_request(&name, number))
+            .await
+            .map_err(ApiError::internal)?;
+
+        match pull {
+            Some(pull) => Ok(freshness.ok().json(pull)),
+            None => Err(ApiError::not_found(format!(
+                "pull request {repository}#{number} not found"
+            ))
+            .into()),
+        }
+    })
+    .await
 }
 
 pub fn handler() -> Resource {
     web::resource("/pullrequest").route(web::get().to(pullrequest))
 }
 
+pub fn detail_handler() -> Resource {
+    web::resource("/pullrequest/{owner}/{name}/{number}").route(web::get().to(pullrequest_detail))
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::pullrequest::pullrequest;
+    use crate::events::Snapshot;
+    use crate::handlers::page::{encode_cursor, CursorQuery};
+    use crate::pullrequest::{
+        pullrequest, pullrequest_detail, query_pull_requests, PullRequestQuery, PullRequestSort,
+        SortOrder,
+    };
+    use crate::scraper::{fixtures, PullRequestCollection, PullRequestEligibility};
     use crate::RunContext;
-    use actix_web::http;
     use actix_web::test::TestRequest;
     use actix_web::web::Data;
+    use actix_web::{body, http};
+    use chrono::Utc;
     use std::sync::Mutex;
 
+    /// Opened on `day` of October, merged the same day if that's odd.
+    fn pull(repository: &str, number: i64, author: &str, day: u32) -> PullRequestCollection {
+        let created_at = format!("2023-10-{day:02}T00:00:00Z").parse().unwrap();
+        let mut pull = if day % 2 == 0 {
+            fixtures::open_pull(repository, number, author)
+        } else {
+            fixtures::pull(repository, number, author)
+        };
+        pull.created_at = created_at;
+        pull.updated_at = created_at;
+        if pull.merged {
+            pull.merged_at = created_at;
+            pull.closed_at = created_at;
+        }
+        pull
+    }
+
+    fn pulls() -> Vec<PullRequestCollection> {
+        vec![
+            pull("teknologi-umum/pehape", 1, "somebody", 2),
+            pull("teknologi-umum/pehape", 2, "Somebody", 3),
+            pull("teknologi-umum/bot", 1, "other", 5),
+            pull("teknologi-umum/bot", 2, "somebody", 5),
+        ]
+    }
+
+    fn cursor(uri: &str) -> CursorQuery {
+        CursorQuery::from_request(&TestRequest::default().uri(uri).to_http_request()).unwrap()
+    }
+
+    #[test]
+    fn test_query_pull_requests() {
+        let numbers = |page: &[PullRequestCollection]| -> Vec<(String, i64)> {
+            page.iter()
+                .map(|pull| (pull.repository.clone(), pull.number))
+                .collect()
+        };
+
+        let by_author = PullRequestQuery {
+            author: Some("SOMEBODY".to_owned()),
+            ..Default::default()
+        };
+        let first =
+            query_pull_requests(pulls(), &by_author, &cursor("/pullrequest?limit=2")).unwrap();
+        assert_eq!(first.total, 3);
+        assert_eq!(
+            numbers(&first.items),
+            vec![
+                ("teknologi-umum/bot".to_owned(), 2),
+                ("teknologi-umum/pehape".to_owned(), 2)
+            ]
+        );
+        let next = format!("/pullrequest?limit=2&cursor={}", first.next_cursor.unwrap());
+        let second = query_pull_requests(pulls(), &by_author, &cursor(&next)).unwrap();
+        assert_eq!(
+            numbers(&second.items),
+            vec![("teknologi-umum/pehape".to_owned(), 1)]
+        );
+        assert!(second.next_cursor.is_none());
+
+        let merged = PullRequestQuery {
+            repo: Some("teknologi-umum/BOT".to_owned()),
+            eligibility: Some(PullRequestEligibility::Accepted),
+            sort: PullRequestSort::MergedAt,
+            order: SortOrder::Asc,
+            ..Default::default()
+        };
+        let page = query_pull_requests(pulls(), &merged, &cursor("/pullrequest")).unwrap();
+        assert_eq!(
+            numbers(&page.items),
+            vec![
+                ("teknologi-umum/bot".to_owned(), 1),
+                ("teknologi-umum/bot".to_owned(), 2)
+            ]
+        );
+
+        let since = PullRequestQuery {
+            since: Some("2023-10-03T00:00:00Z".parse().unwrap()),
+            until: Some("2023-10-04T00:00:00Z".parse().unwrap()),
+            ..Default::default()
+        };
+        let page = query_pull_requests(pulls(), &since, &cursor("/pullrequest")).unwrap();
+        assert_eq!(
+            numbers(&page.items),
+            vec![("teknologi-umum/pehape".to_owned(), 2)]
+        );
+
+        let bad = cursor(&format!("/pullrequest?cursor={}", encode_cursor("x")));
+        assert!(query_pull_requests(pulls(), &since, &bad).is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_pullrequest_detail() {
+        let ctx = RunContext::default();
+        let snapshot = Snapshot {
+            repositories: vec![],
+            contributors: vec![],
+            pulls: pulls(),
+        };
+        ctx.store
+            .save_scrape(Utc::now(), &snapshot, &[], vec![])
+            .unwrap();
+        let ctx = Data::new(Mutex::new(ctx));
+        let req = |number: &'static str| {
+            TestRequest::default()
+                .param("owner", "teknologi-umum")
+                .param("name", "BOT")
+                .param("number", number)
+                .to_http_request()
+        };
+
+        let resp = pullrequest_detail(ctx.clone(), req("2"))
+            .await
+            .expect("an error occurred");
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        let bytes = body::to_bytes(resp.into_body()).await.unwrap();
+        let pull: PullRequestCollection = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(pull.user.login, "somebody");
+
+        let resp = pullrequest_detail(ctx.clone(), req("3")).await;
+        assert_eq!(
+            resp.unwrap_err().error_response().status(),
+            http::StatusCode::NOT_FOUND
+        );
+        assert!(pullrequest_detail(ctx, req("two")).await.is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_pullrequest_invalid_query() {
+        let ctx = Data::new(Mutex::new(RunContext::default()));
+        for uri in [
+            "/pullrequest?state=merged",
+            "/pullrequest?sort=title",
+            "/pullrequest?limit=0",
+        ] {
+            let req = TestRequest::default().uri(uri).to_http_request();
+            assert!(pullrequest(ctx.clone(), req).await.is_err(), "{uri}");
+        }
+    }
+
     #[actix_web::test]
     async fn test_pullrequest() {
         let ctx = Data::new(Mutex::new(RunContext::default()));
@@ -1,54 +1,169 @@
 use actix_web::{
-    http,
     web::{self, Data},
     HttpRequest, HttpResponse, Resource, Result,
 };
-use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::cmp::Reverse;
 use std::sync::Mutex;
 
-use crate::{github::Issue, RunContext};
+use crate::handlers::error::ApiError;
+use crate::lock::LockRecover;
+use crate::{
+    handlers::caching::Freshness,
+    handlers::page::{Page, PageQuery},
+    scraper::RepositoryCollection,
+    store::Store,
+    RunContext,
+};
+
+/// Most first, ties are broken by name.
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq, Debug, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RepositorySort {
+    #[default]
+    Updated,
+    Stars,
+    Issues,
+}
+
+#[derive(Deserialize, Default, JsonSchema)]
+pub struct RepositoriesQuery {
+    /// Case-insensitive, any of the repository's languages.
+    pub language: Option<String>,
+    pub topic: Option<String>,
+    pub has_issues: Option<bool>,
+    /// Case-insensitive search in the name and description.
+    pub q: Option<String>,
+    #[serde(default)]
+    pub sort: RepositorySort,
+}
 
-#[derive(Serialize, Deserialize)]
-pub struct RepositoriesResponse {
-    pub full_name: String,
-    pub html_url: String,
-    pub description: String,
-    pub languages: Vec<String>,
-    pub stars_count: i64,
-    pub forks_count: i64,
-    pub topics: Vec<String>,
-    pub created_at: DateTime<Utc>,
-    pub updated_at: DateTime<Utc>,
-    pub issues: Vec<Issue>,
+impl RepositoriesQuery {
+    fn matches(&self, repo: &RepositoryCollection) -> bool {
+        if let Some(language) = &self.language {
+            if !repo
+                .languages
+                .iter()
+                .any(|l| l.eq_ignore_ascii_case(language))
+            {
+                return false;
+            }
+        }
+        if let Some(topic) = &self.topic {
+            if !repo.topics.contains(topic) {
+                return false;
+            }
+        }
+        if let Some(has_issues) = self.has_issues {
+            if repo.issues.is_empty() == has_issues {
+                return false;
+            }
+        }
+        if let Some(q) = &self.q {
+            let q = q.to_lowercase();
+            let description = repo.description.as_deref().unwrap_or_default();
+            if !repo.full_name.to_lowercase().contains(&q)
+                && !description.to_lowercase().contains(&q)
+            {
+                return false;
+            }
+        }
+        true
+    }
 }
 
-async fn repositories(ctx: Data<Mutex<RunContext<'_>>>, _req: HttpRequest) -> Result<HttpResponse> {
-    let unlocked_ctx = ctx.lock().unwrap();
-    let unlocked_map = &unlocked_ctx.config.borrow().cached_map;
-    let cached: String = match unlocked_map.get("repo") {
-        Some(cached_repo) => cached_repo.into(),
-        _ => "[]".into(),
-    };
+pub fn filter_repositories(
+    repositories: Vec<RepositoryCollection>,
+    query: &RepositoriesQuery,
+) -> Vec<RepositoryCollection> {
+    let mut repositories: Vec<RepositoryCollection> = repositories
+        .into_iter()
+        .filter(|repo| query.matches(repo))
+        .collect();
+    repositories.sort_by(|a, b| a.full_name.cmp(&b.full_name));
+    match query.sort {
+        RepositorySort::Updated => repositories.sort_by_key(|repo| Reverse(repo.updated_at)),
+        RepositorySort::Stars => repositories.sort_by_key(|repo| Reverse(repo.stars_count)),
+        RepositorySort::Issues => repositories.sort_by_key(|repo| Reverse(repo.issues.len())),
+    }
+    repositories
+}
 
-    Ok(HttpResponse::Ok()
-        .content_type(http::header::ContentType::json())
-        .body(cached))
+async fn repositories(ctx: Data<Mutex<RunContext<'_>>>, req: HttpRequest) -> Result<HttpResponse> {
+    let query = web::Query::<RepositoriesQuery>::from_query(req.query_string())
+        .map_err(ApiError::bad_request)?;
+    let page = PageQuery::from_request(&req)?;
+    let body_cache = ctx.lock_recover().body_cache.clone();
+    let (body_cache, req, query, page) = (&body_cache, &req, &query, &page);
+
+    Freshness::serve(&ctx, req, |store, freshness| async move {
+        body_cache
+            .json(req, &freshness, async {
+                let repositories = store
+                    .blocking(Store::repositories)
+                    .await
+                    .map_err(ApiError::internal)?;
+                let repositories = filter_repositories(repositories, query);
+                Ok(Page::paginate(repositories, page))
+            })
+            .await
+    })
+    .await
+}
+
+async fn repository(ctx: Data<Mutex<RunContext<'_>>>, req: HttpRequest) -> Result<HttpResponse> {
+    let full_name = format!(
+        "{}/{}",
+        req.match_info().query("owner"),
+        req.match_info().query("name")
+    );
+    let full_name = &full_name;
+
+    Freshness::serve(&ctx, &req, |store, freshness| async move {
+        let name = full_name.clone();
+        let repository = store
+            .blocking(move |store| store.repository(&name))
+            .await
+            .map_err(ApiError::internal)?;
+
+        match repository {
+            Some(repository) => Ok(freshness.ok().json(repository)),
+            None => Err(ApiError::not_found(format!("repository {full_name} not found")).into()),
+        }
+    })
+    .await
 }
 
 pub fn handler() -> Resource {
     web::resource("/repo").route(web::get().to(repositories))
 }
 
+pub fn detail_handler() -> Resource {
+    web::resource("/repo/{owner}/{name}").route(web::get().to(repository))
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::Mutex;
 
-    use actix_web::{http, test::TestRequest, web::Data};
+    use actix_web::{body, http, test::TestRequest, web::Data};
+    use chrono::Utc;
 
+    use crate::events::Snapshot;
+    use crate::scraper::{fixtures, RepositoryCollection};
     use crate::RunContext;
 
-    use super::repositories;
+    use super::{filter_repositories, repositories, RepositoriesQuery, RepositorySort};
+
+    fn repository(name: &str, language: &str, stars: i64, issues: usize) -> RepositoryCollection {
+        RepositoryCollection {
+            description: Some(format!("The {name} project")),
+            languages: vec![language.to_owned()],
+            stars_count: stars,
+            ..fixtures::repository(name, issues)
+        }
+    }
 
     #[actix_web::test]
     async fn test_repositories() {
@@ -60,4 +175,107 @@ mod tests {
             http::StatusCode::OK
         );
     }
+
+    #[test]
+    fn test_filter_repositories() {
+        let names = |query: RepositoriesQuery| -> Vec<String> {
+            let repositories = vec![
+                repository("pehape", "PHP", 5, 0),
+                repository("bot", "TypeScript", 10, 2),
+                repository("graphene", "Go", 1, 3),
+            ];
+            filter_repositories(repositories, &query)
+                .into_iter()
+                .map(|repo| repo.full_name)
+                .collect()
+        };
+
+        let stars = RepositoriesQuery {
+            sort: RepositorySort::Stars,
+            ..Default::default()
+        };
+        assert_eq!(
+            names(stars),
+            vec![
+                "teknologi-umum/bot",
+                "teknologi-umum/pehape",
+                "teknologi-umum/graphene"
+            ]
+        );
+
+        let with_issues = RepositoriesQuery {
+            has_issues: Some(true),
+            sort: RepositorySort::Issues,
+            ..Default::default()
+        };
+        assert_eq!(
+            names(with_issues),
+            vec!["teknologi-umum/graphene", "teknologi-umum/bot"]
+        );
+
+        let php = RepositoriesQuery {
+            language: Some("php".to_owned()),
+            q: Some("PEHAPE PROJECT".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(names(php), vec!["teknologi-umum/pehape"]);
+    }
+
+    #[actix_web::test]
+    async fn test_repository() {
+        let ctx = RunContext::default();
+        let snapshot = Snapshot {
+            repositories: vec![repository("pehape", "PHP", 5, 2)],
+            contributors: vec![],
+            pulls: vec![],
+        };
+        ctx.store
+            .save_scrape(Utc::now(), &snapshot, &[], vec![])
+            .unwrap();
+        let ctx = Data::new(Mutex::new(ctx));
+
+        let req = TestRequest::default()
+            .param("owner", "Teknologi-Umum")
+            .param("name", "pehape")
+            .to_http_request();
+        let resp = super::repository(ctx.clone(), req)
+            .await
+            .expect("an error occurred");
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        let bytes = body::to_bytes(resp.into_body()).await.unwrap();
+        let found: RepositoryCollection = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(found.full_name, "teknologi-umum/pehape");
+        assert_eq!(found.issues.len(), 2);
+
+        let req = TestRequest::default()
+            .param("owner", "teknologi-umum")
+            .param("name", "nothing")
+            .to_http_request();
+        let resp = super::repository(ctx, req)
+            .await
+            .unwrap_err()
+            .error_response();
+        assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+        let bytes = body::to_bytes(resp.into_body()).await.unwrap();
+        let error: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(error["code"], "not_found");
+        assert_eq!(
+            error["message"],
+            "repository teknologi-umum/nothing not found"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_repositories_invalid_query() {
+        let ctx = Data::new(Mutex::new(RunContext::default()));
+        for uri in [
+            "/repo?sort=name",
+            "/repo?has_issues=maybe",
+            "/repo?per_page=0",
+            "/repo?page=184467440737095517",
+        ] {
+            let req = TestRequest::default().uri(uri).to_http_request();
+            assert!(repositories(ctx.clone(), req).await.is_err(), "{uri}");
+        }
+    }
 }
@@ -0,0 +1,275 @@
+use actix_web::{
+    http::header,
+    web::{self, Bytes, Data},
+    HttpRequest, HttpResponse, Resource, Result,
+};
+use futures_util::stream::{self, Stream};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::time::{self, Interval, MissedTickBehavior};
+
+use crate::events::Event;
+use crate::handlers::error::ApiError;
+use crate::lock::LockRecover;
+use crate::store::{Store, StoreError};
+use crate::RunContext;
+
+/// Often enough for proxies not to drop an idle connection.
+const HEARTBEAT: Duration = Duration::from_secs(15);
+/// How long `EventSource` waits before reconnecting, in milliseconds.
+const RETRY_MS: u64 = 5000;
+
+#[derive(Deserialize, JsonSchema)]
+pub struct StreamQuery {
+    /// Resume after this event, for clients that can't send `Last-Event-ID`.
+    pub last_event_id: Option<u64>,
+}
+
+/// One SSE message, named after the event's `type`.
+fn frame(event: &Event) -> Result<Bytes, StoreError> {
+    let data = serde_json::to_value(event)?;
+    let name = data["type"].as_str().unwrap_or("message");
+    Ok(Bytes::from(format!(
+        "id: {}\nevent: {name}\ndata: {data}\n\n",
+        event.id
+    )))
+}
+
+/// Events for one client: the ones it missed first, then live ones as the store
+/// records them, deduplicated by id.
+struct Subscription {
+    store: Arc<Store>,
+    receiver: broadcast::Receiver<Event>,
+    pending: VecDeque<Event>,
+    last_id: Option<u64>,
+    /// Newest stored event when the client subscribed, where a client that sent
+    /// no `Last-Event-ID` and hasn't been sent anything yet resumes after lagging.
+    subscribed_after: u64,
+    heartbeat: Interval,
+}
+
+impl Subscription {
+    async fn next(&mut self) -> Option<Result<Bytes, StoreError>> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                if self.last_id.is_some_and(|id| event.id <= id) {
+                    continue;
+                }
+                self.last_id = Some(event.id);
+                return Some(frame(&event));
+            }
+
+            tokio::select! {
+                received = self.receiver.recv() => match received {
+                    Ok(event) => self.pending.push_back(event),
+                    // dropped by the channel, still in the store
+                    Err(RecvError::Lagged(_)) => {
+                        let after = self.last_id.unwrap_or(self.subscribed_after);
+                        let missed = self
+                            .store
+                            .blocking(move |store| store.events_after(after))
+                            .await;
+                        match missed {
+                            Ok(missed) => self.pending.extend(missed),
+                            Err(e) => return Some(Err(e)),
+                        }
+                    }
+                    Err(RecvError::Closed) => return None,
+                },
+                _ = self.heartbeat.tick() => {
+                    return Some(Ok(Bytes::from_static(b": heartbeat\n\n")));
+                }
+            }
+        }
+    }
+}
+
+/// The SSE body for a client that last saw `last_event_id`. When that event is no
+/// longer stored, the client missed more than we can replay and gets a `reset`,
+/// it should refetch what it shows.
+async fn event_stream(
+    store: Arc<Store>,
+    last_event_id: Option<u64>,
+    heartbeat: Duration,
+) -> Result<impl Stream<Item = Result<Bytes, StoreError>>, StoreError> {
+    // before reading the backlog, so nothing recorded in between is lost
+    let receiver = store.subscribe();
+    let subscribed_after = store.blocking(Store::newest_event_id).await?;
+
+    let mut preamble = format!("retry: {RETRY_MS}\n\n");
+    let mut pending = VecDeque::new();
+    if let Some(id) = last_event_id {
+        let missed = store.blocking(move |store| store.events_after(id)).await?;
+        if missed.first().is_some_and(|first| first.id > id + 1) {
+            preamble.push_str("event: reset\ndata: {}\n\n");
+        }
+        pending.extend(missed);
+    }
+
+    let mut heartbeat = time::interval_at(time::Instant::now() + heartbeat, heartbeat);
+    heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let subscription = Subscription {
+        store,
+        receiver,
+        pending,
+        last_id: last_event_id,
+        subscribed_after,
+        heartbeat,
+    };
+
+    let preamble = stream::once(async move { Ok(Bytes::from(preamble)) });
+    let events = stream::unfold(subscription, |mut subscription| async move {
+        let item = subscription.next().await?;
+        Some((item, subscription))
+    });
+    Ok(stream::StreamExt::chain(preamble, events))
+}
+
+fn last_event_id(req: &HttpRequest) -> Result<Option<u64>> {
+    if let Some(value) = req.headers().get("Last-Event-ID") {
+        let id = value
+            .to_str()
+            .ok()
+            .and_then(|value| value.trim().parse().ok())
+            .ok_or_else(|| ApiError::bad_request("Last-Event-ID must be an event id"))?;
+        return Ok(Some(id));
+    }
+    let query =
+        web::Query::<StreamQuery>::from_query(req.query_string()).map_err(ApiError::bad_request)?;
+    Ok(query.last_event_id)
+}
+
+async fn event_source(ctx: Data<Mutex<RunContext<'_>>>, req: HttpRequest) -> Result<HttpResponse> {
+    let last_event_id = last_event_id(&req)?;
+    let store = ctx.lock_recover().store.clone();
+    let events = event_stream(store, last_event_id, HEARTBEAT)
+        .await
+        .map_err(ApiError::internal)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        // compressing would hold events back until a buffer fills
+        .insert_header((header::CONTENT_ENCODING, "identity"))
+        // nor should nginx buffer them
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(events))
+}
+
+pub fn handler() -> Resource {
+    web::resource("/stream").route(web::get().to(event_source))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use actix_web::{http, test::TestRequest, web::Bytes, web::Data};
+    use chrono::Utc;
+    use futures_util::{Stream, StreamExt};
+
+    use crate::events::{EventKind, MAX_EVENTS};
+    use crate::store::{Store, StoreError};
+    use crate::RunContext;
+
+    use super::{event_source, event_stream};
+
+    fn merged(number: i64) -> EventKind {
+        EventKind::PullRequestMerged {
+            repository: "teknologi-umum/pehape".to_owned(),
+            number,
+            html_url: format!("https://github.com/teknologi-umum/pehape/pull/{number}"),
+            title: "Add tests".to_owned(),
+            author: "somebody".to_owned(),
+        }
+    }
+
+    async fn next(events: &mut (impl Stream<Item = Result<Bytes, StoreError>> + Unpin)) -> String {
+        let chunk = events.next().await.unwrap().unwrap();
+        String::from_utf8(chunk.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_event_stream() {
+        let store = Arc::new(Store::open_in_memory().unwrap());
+        store
+            .record_events(vec![merged(1), merged(2)], Utc::now())
+            .unwrap();
+
+        let events = event_stream(store.clone(), Some(1), Duration::from_millis(50))
+            .await
+            .unwrap();
+        let mut events = Box::pin(events);
+
+        assert_eq!(next(&mut events).await, "retry: 5000\n\n");
+        let missed = next(&mut events).await;
+        assert!(missed.starts_with("id: 2\nevent: PullRequestMerged\ndata: {"));
+        assert!(missed.contains(r#""number":2"#), "{missed}");
+
+        store.record_events(vec![merged(3)], Utc::now()).unwrap();
+        assert!(next(&mut events).await.starts_with("id: 3\n"));
+        assert_eq!(next(&mut events).await, ": heartbeat\n\n");
+    }
+
+    #[tokio::test]
+    async fn test_event_stream_reset() {
+        let store = Arc::new(Store::open_in_memory().unwrap());
+        store.record_events(vec![merged(1)], Utc::now()).unwrap();
+        let preamble = |store: Arc<Store>| async move {
+            let events = event_stream(store, Some(0), Duration::from_secs(60))
+                .await
+                .unwrap();
+            next(&mut Box::pin(events)).await
+        };
+
+        assert_eq!(preamble(store.clone()).await, "retry: 5000\n\n");
+
+        // event 1 is trimmed
+        store
+            .record_events(vec![merged(2); MAX_EVENTS], Utc::now())
+            .unwrap();
+        assert_eq!(
+            preamble(store).await,
+            "retry: 5000\n\nevent: reset\ndata: {}\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_event_stream_lagged() {
+        let store = Arc::new(Store::open_in_memory().unwrap());
+        store
+            .record_events(vec![merged(1), merged(2)], Utc::now())
+            .unwrap();
+
+        let events = event_stream(store.clone(), None, Duration::from_secs(60))
+            .await
+            .unwrap();
+        let mut events = Box::pin(events);
+        assert_eq!(next(&mut events).await, "retry: 5000\n\n");
+
+        // more than the channel holds, the client catches up from the store, but
+        // not with what was there before it subscribed
+        store
+            .record_events(vec![merged(3); MAX_EVENTS], Utc::now())
+            .unwrap();
+        assert!(next(&mut events).await.starts_with("id: 3\n"));
+    }
+
+    #[actix_web::test]
+    async fn test_invalid_last_event_id() {
+        let ctx = Data::new(Mutex::new(RunContext::default()));
+        let req = TestRequest::default()
+            .insert_header(("Last-Event-ID", "yesterday"))
+            .to_http_request();
+        let resp = event_source(ctx, req).await;
+        assert_eq!(
+            resp.unwrap_err().error_response().status(),
+            http::StatusCode::BAD_REQUEST
+        );
+    }
+}
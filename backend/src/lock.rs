@@ -0,0 +1,37 @@
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Locks a mutex even if a thread panicked while holding it. What we guard is
+/// either swapped whole, like a reloaded config, or rolled back while unwinding,
+/// like a store transaction, so a panic can't leave it half-written, and one
+/// failed request shouldn't take every later one down.
+pub trait LockRecover<T> {
+    fn lock_recover(&self) -> MutexGuard<'_, T>;
+}
+
+impl<T> LockRecover<T> for Mutex<T> {
+    fn lock_recover(&self) -> MutexGuard<'_, T> {
+        self.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use super::LockRecover;
+
+    #[test]
+    fn test_lock_recover() {
+        let mutex = Arc::new(Mutex::new(1));
+        let poisoner = mutex.clone();
+        let _ = thread::spawn(move || {
+            let _guard = poisoner.lock().unwrap();
+            panic!("poison");
+        })
+        .join();
+
+        assert!(mutex.is_poisoned());
+        assert_eq!(*mutex.lock_recover(), 1);
+    }
+}
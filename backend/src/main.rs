@@ -1,23 +1,38 @@
 use crate::github::Github;
-use actix_web::web::Data;
-use actix_web::{App, HttpServer, Result};
+use crate::lock::LockRecover;
+use actix_web::web::{self, Data};
+use actix_web::{middleware, App, HttpServer, Result};
 use backoff::exponential::ExponentialBackoff;
 use backoff::SystemClock;
 use chrono::{DateTime, NaiveDate, Utc};
-use clap::clap_app;
-use config::Config;
+use config::{Config, LegacyState};
+use handlers::compressed::BodyCache;
 use lazy_static::lazy_static;
-use scopeguard::defer;
 use scraper::run_scrape;
+use secret::Secret;
+use settings::Settings;
 use std::cell::RefCell;
+use std::fs;
+use std::path::Path;
 use std::process::exit;
 use std::sync::{Arc, Mutex};
 use std::{env, io, usize};
+use store::Store;
 
 mod config;
+mod events;
 mod github;
 mod handlers;
+mod lock;
+mod migration;
+mod notifier;
+mod persist;
+mod reload;
 mod scraper;
+mod secret;
+mod settings;
+mod spam;
+mod store;
 
 use crate::handlers::*;
 
@@ -28,6 +43,9 @@ lazy_static! {
         DateTime::<Utc>::from_utc(NaiveDate::from_ymd(2023, 10, 31).and_hms(23, 59, 59), Utc);
 }
 
+/// Number of accepted pull requests a contributor needs to complete the event.
+pub const PULL_REQUEST_GOAL: i64 = 4;
+
 #[tokio::main]
 async fn main() {
     if cfg!(debug_assertions) {
@@ -49,11 +67,20 @@ pub struct RunContext<'a> {
     pub listen_address: String,
     pub num_workers: usize,
     pub scrape_interval: u64,
-    pub github_token: String,
+    pub github_token: Secret,
 
+    // read-only, edited by humans
     pub config_path: String,
     pub config: RefCell<Box<Config>>,
 
+    // written by the daemon only
+    pub state_dir: String,
+    pub store: Arc<Store>,
+    // encoded bodies of list endpoints, valid until the next scrape
+    pub body_cache: Arc<BodyCache>,
+    // store copies kept in `state_dir/backups`, rotated after every scrape
+    pub backup_count: usize,
+
     pub scrape_per_page: u8,
 
     // placeholder
@@ -64,103 +91,113 @@ impl<'a> RunContext<'a> {
     pub fn default() -> Self {
         Self {
             inner: RefCell::new(Box::new(&RunContextInner {})),
-            listen_address: "127.0.0.1:8080".to_owned(),
-            num_workers: 1,
-            scrape_interval: 3600,
-            github_token: "".to_owned(),
-            config_path: "/tmp/data.yml".to_owned(),
+            listen_address: settings::DEFAULT_LISTEN_ADDRESS.to_owned(),
+            num_workers: settings::DEFAULT_NUM_WORKERS,
+            scrape_interval: settings::DEFAULT_SCRAPE_INTERVAL,
+            github_token: Secret::default(),
+            config_path: settings::DEFAULT_CONFIG_PATH.to_owned(),
             config: RefCell::new(Config::default()),
-            scrape_per_page: 100,
+            state_dir: settings::DEFAULT_STATE_DIR.to_owned(),
+            store: Arc::new(Store::open_in_memory().expect("failed to open in-memory store")),
+            body_cache: Arc::new(BodyCache::default()),
+            backup_count: settings::DEFAULT_BACKUP_COUNT,
+            scrape_per_page: settings::DEFAULT_SCRAPE_PER_PAGE,
         }
     }
-
-    pub fn save_cfg(&self) -> anyhow::Result<Box<Config>> {
-        self.config.borrow().clone().save_yaml_to(&self.config_path)
-    }
 }
 
 pub type RRunContext<'a> = Arc<Mutex<RunContext<'a>>>;
 
-async fn run<'a>() -> Result<()> {
-    let app = clap_app!(hacktoberfestd =>
-        (version: "")
-        (about: "Hacktoberfest serverd")
-        (@arg addr: --addr +takes_value "Listen address for HTTP server")
-        (@arg wrk: --wrk +takes_value "Number of HTTP server workers")
-        (@arg scrape_interval: --("scrape_interval") +takes_value "Scrap interval in second")
-        (@arg github_token: --("github_token") +takes_value "Github API Token")
-        (@arg config_path: --("config_path") +takes_value "Config path")
-        (@arg scrape_per_page: --("scrape_per_page") +takes_value "Github scrap per_page limit")
-    )
-    .get_matches();
-
-    let default_config = RunContext::default();
-
-    let fallback_laddr = env::var("LISTEN_ADDR").unwrap_or(default_config.listen_address);
-    let fallback_num_wrk_str =
-        env::var("NUM_WORKERS").unwrap_or(default_config.num_workers.to_string());
-    let fallback_num_wrk = fallback_num_wrk_str
-        .parse::<usize>()
-        .unwrap_or(default_config.num_workers);
-    let fallback_scrape_interval_str =
-        env::var("scrape_interval").unwrap_or(default_config.scrape_interval.to_string());
-    let fallback_scrape_interval = fallback_scrape_interval_str
-        .parse::<u64>()
-        .unwrap_or(default_config.scrape_interval);
-    let fallback_github_token = env::var("GITHUB_TOKEN").unwrap_or(default_config.github_token);
-    let fallback_config_path = env::var("CONFIG_PATH").unwrap_or(default_config.config_path);
-    let fallback_scrape_per_page_str =
-        env::var("scrape_per_page").unwrap_or(default_config.scrape_per_page.to_string());
-    let fallback_scrape_per_page = fallback_scrape_per_page_str
-        .parse::<u8>()
-        .unwrap_or(default_config.scrape_per_page);
-
-    let laddr: String = app.get_one("addr").unwrap_or(&fallback_laddr).to_string();
-    let github_token: String = app
-        .get_one("github_token")
-        .unwrap_or(&fallback_github_token)
-        .to_string();
-    let num_workers: usize = *app.get_one("wrk").unwrap_or(&fallback_num_wrk);
-    let scrape_interval: u64 = *app
-        .get_one("scrape_interval")
-        .unwrap_or(&fallback_scrape_interval);
-    let config_path = app
-        .get_one("config_path")
-        .unwrap_or(&fallback_config_path)
-        .to_string();
-    let scrape_per_page: u8 = *app
-        .get_one("scrape_per_page")
-        .unwrap_or(&fallback_scrape_per_page);
-
-    let conf = RefCell::new(Config::load_or_create(&config_path).unwrap());
-    let write_back_conf_path = config_path.clone();
+async fn run<'a>() -> anyhow::Result<()> {
+    let app = settings::app().get_matches();
+    let env_var = |name: &str| env::var(name).ok();
+    let config_path = Settings::config_path(&app, &env_var)?;
+
+    match app.subcommand() {
+        Some(("validate-config", _)) => exit(validate_config(&config_path.value)),
+        Some(("migrate-config", args)) => {
+            migrate_config(&config_path.value, args.is_present("dry_run"))?;
+            exit(0);
+        }
+        Some(("config-schema", _)) => {
+            println!("{}", Config::json_schema()?);
+            exit(0);
+        }
+        Some(("openapi", _)) => {
+            println!("{}", serde_json::to_string_pretty(&openapi::spec())?);
+            exit(0);
+        }
+        _ => {}
+    }
 
+    if app.is_present("print_config") {
+        // only reads, nothing is migrated or created
+        let server = Config::load(&config_path.value)?
+            .map(|conf| conf.server)
+            .unwrap_or_default();
+        print!("{}", Settings::load(&app, &env_var, config_path, &server)?);
+        exit(0);
+    }
+
+    // configs written by older versions also hold runtime state, move it to the store
+    let legacy = LegacyState::migrate(&config_path.value)?;
+    if legacy.is_some() {
+        println!(
+            "migrated runtime state out of {0}, backup at {0}.bak",
+            config_path.value
+        );
+    }
+    let conf = Config::load_or_create(&config_path.value)?;
+
+    let settings = Settings::load(&app, &env_var, config_path, &conf.server)?;
+
+    let state_dir = settings.state_dir.value.clone();
+    fs::create_dir_all(Path::new(&state_dir).join("backups"))?;
+    let store_path = Path::new(&state_dir)
+        .join("data.db")
+        .to_string_lossy()
+        .into_owned();
+    let store = Arc::new(Store::open(&store_path)?);
+    if let Some(legacy) = legacy.filter(|legacy| !legacy.is_empty()) {
+        if store.import_legacy(&legacy)? {
+            println!("> imported cached scrape into {store_path}");
+        }
+    }
+    let conf = conf.with_target_overrides(store.target_overrides()?)?;
+
+    let github_token = settings.github_token.value.clone();
+    if !github_token.is_empty() {
+        check_github_token(&github_token).await?;
+    }
     let env = Arc::new(Mutex::new(RunContext {
-        inner: default_config.inner,
-        listen_address: laddr.into(),
-        num_workers,
-        scrape_interval,
-        config_path,
+        inner: RefCell::new(Box::new(&RunContextInner {})),
+        listen_address: settings.listen_address.value,
+        num_workers: settings.num_workers.value,
+        scrape_interval: settings.scrape_interval.value,
+        config_path: settings.config_path.value,
         github_token: github_token.clone(),
-        config: RefCell::clone(&conf),
+        config: RefCell::new(conf),
+        state_dir,
+        store,
+        body_cache: Arc::new(BodyCache::default()),
+        backup_count: settings.backup_count.value,
 
-        scrape_per_page: scrape_per_page,
+        scrape_per_page: settings.scrape_per_page.value,
     }));
 
-    let defer_ctx = env.clone();
-    defer! {
-        let local_env = defer_ctx.lock().unwrap();
-        println!("write-back config @ {write_back_conf_path}");
-        local_env.save_cfg().unwrap();
-        println!("> OK");
-    }
+    let reload_ctx = env.clone();
+    tokio::spawn(async move {
+        if let Err(e) = reload::watch(reload_ctx).await {
+            log::error!("config hot reload disabled: {e}");
+        }
+    });
 
     let scrape_thread_ctx = env.clone();
     tokio::spawn(async move {
         let github_client = if github_token.is_empty() {
             Github::new()
         } else {
-            Github::new_with_token(Some(String::from(github_token)))
+            Github::new_with_token(Some(github_token.expose().to_owned()))
         };
 
         let exponential_backoff_box: Box<ExponentialBackoff<SystemClock>> =
@@ -188,19 +225,128 @@ async fn run<'a>() -> Result<()> {
     }
 }
 
+/// Runs the same checks as loading the config, printing every issue.
+fn validate_config(path: &String) -> i32 {
+    let config = match fs::read_to_string(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|contents| Config::parse(&contents))
+    {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("error: {path}: {e}");
+            return 1;
+        }
+    };
+
+    let report = config.check();
+    for warning in report.warnings.iter() {
+        eprintln!("warning: {warning}");
+    }
+    for error in report.errors.iter() {
+        eprintln!("error: {error}");
+    }
+    if report.errors.is_empty() {
+        println!("{path}: OK");
+        0
+    } else {
+        1
+    }
+}
+
+fn migrate_config(path: &String, dry_run: bool) -> anyhow::Result<()> {
+    let contents = fs::read_to_string(path)?;
+    let migrated = migration::migrate(serde_yaml::from_str(&contents)?)?;
+    let yaml = serde_yaml::to_string(&migrated.value)?;
+    // refuse to write something the daemon won't load
+    Config::from_yaml(&yaml)?;
+
+    if dry_run {
+        print!("{yaml}");
+    } else if !migrated.is_migrated() {
+        println!("{path} is already version {}", migration::CONFIG_VERSION);
+    } else {
+        persist::write_atomic(Path::new(&format!("{path}.bak")), contents.as_bytes())?;
+        persist::write_atomic(Path::new(path), yaml.as_bytes())?;
+        println!(
+            "{path} migrated from version {} to {}, backup at {path}.bak",
+            migrated.from,
+            migration::CONFIG_VERSION
+        );
+    }
+    Ok(())
+}
+
+/// Fails on a token GitHub rejects, rather than scraping unauthenticated at 60
+/// requests per hour, and warns about scopes the token doesn't need.
+async fn check_github_token(token: &Secret) -> anyhow::Result<()> {
+    let client = Github::new_with_token(Some(token.expose().to_owned()));
+    match client.token_scopes().await {
+        Ok(Some(scopes)) => {
+            let excess = github::excess_scopes(&scopes);
+            if !excess.is_empty() {
+                log::warn!(
+                    "github token has scopes it doesn't need: {}, a token without scopes is enough",
+                    excess.join(", ")
+                );
+            }
+        }
+        // fine-grained tokens don't report their permissions
+        Ok(None) => {}
+        Err(github::GithubError::StatusCode(status))
+            if status == actix_web::http::StatusCode::UNAUTHORIZED =>
+        {
+            anyhow::bail!("github token was rejected, it may be expired or revoked");
+        }
+        Err(e) => log::warn!("couldn't check the github token: {e}"),
+    }
+    Ok(())
+}
+
 async fn run_server<'a>(env: &'a RRunContext<'static>) -> Result<(), io::Error> {
     let data = Data::from(env.clone());
-    let local_env = env.lock().unwrap().clone(); // don't hold lock!
+    let local_env = env.lock_recover().clone(); // don't hold lock!
+
+    let cors = local_env.config.borrow().cors.clone();
 
     println!("Run server on {}", local_env.listen_address);
     HttpServer::new(move || {
         App::new()
             .app_data(data.clone())
+            .configure(handlers::error::extractor_configs)
+            // leaves bodies `BodyCache` already encoded alone
+            .wrap(middleware::Compress::default())
+            // outermost, so it sees every response and panic
+            .wrap_fn(handlers::error::middleware)
+            .default_service(handlers::error::default_service())
             .service(healthcheck::handler())
             .service(metrics::handler())
-            .service(repositories::handler())
-            .service(contributors::handler())
-            .service(pullrequest::handler())
+            .service(admin::targets_handler())
+            // before `target_handler`, whose key would swallow the suffix
+            .service(admin::ignore_handler())
+            .service(admin::unignore_handler())
+            .service(admin::target_handler())
+            .service(admin::audit_handler())
+            .service(admin::flagged_handler())
+            // public, readable by browsers on other origins
+            .service(
+                web::scope(openapi::V1)
+                    .wrap(middleware::Condition::new(
+                        !cors.allowed_origins.is_empty(),
+                        handlers::cors::middleware(&cors),
+                    ))
+                    .configure(openapi::services),
+            )
+            // unversioned paths kept for clients predating `/v1`; registered last,
+            // the empty prefix matches every path
+            .service(
+                web::scope("")
+                    .wrap(middleware::Condition::new(
+                        !cors.allowed_origins.is_empty(),
+                        handlers::cors::middleware(&cors),
+                    ))
+                    .service(openapi::handler())
+                    .configure(openapi::services),
+            )
     })
     .bind(local_env.listen_address.clone())?
     .workers(local_env.num_workers)
@@ -214,6 +360,7 @@ mod tests {
 
     use scopeguard::defer;
 
+    use crate::config::ScrapeTarget;
     use crate::RunContext;
 
     #[test]
@@ -226,8 +373,8 @@ mod tests {
         }
         ctx.config
             .borrow_mut()
-            .cached_map
-            .insert("todo".to_owned(), "asdaf".to_owned());
+            .scrape_target
+            .push(ScrapeTarget::user("todo".to_owned()));
     }
 
     #[tokio::test]
@@ -0,0 +1,103 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde_yaml::{Mapping, Value};
+
+/// Config schema version written by this build. Version 1 is the unversioned
+/// format from before the `version` key existed.
+pub const CONFIG_VERSION: u64 = 2;
+
+type Migration = fn(&mut Mapping) -> Result<()>;
+
+/// `MIGRATIONS[i]` upgrades a version `i + 1` config to version `i + 2`. Append a
+/// function here, and bump `CONFIG_VERSION`, whenever the format changes in a way
+/// older files don't parse into.
+const MIGRATIONS: [Migration; (CONFIG_VERSION - 1) as usize] = [v1_to_v2];
+
+/// Version 2 only introduced the `version` key itself.
+fn v1_to_v2(_config: &mut Mapping) -> Result<()> {
+    Ok(())
+}
+
+pub struct Migrated {
+    pub value: Value,
+    // version of the file before migrating
+    pub from: u64,
+}
+
+impl Migrated {
+    pub fn is_migrated(&self) -> bool {
+        self.from != CONFIG_VERSION
+    }
+}
+
+fn version_of(config: &Mapping) -> Result<u64> {
+    match config.get("version") {
+        None => Ok(1),
+        Some(version) => version
+            .as_u64()
+            .filter(|version| *version >= 1)
+            .ok_or_else(|| anyhow!("version: expected a positive integer, got {version:?}")),
+    }
+}
+
+/// Upgrades a parsed config file to `CONFIG_VERSION`. Keys the migrations don't
+/// touch are kept as they are.
+pub fn migrate(mut value: Value) -> Result<Migrated> {
+    let config = match value.as_mapping_mut() {
+        Some(config) => config,
+        None => bail!("config must be a mapping"),
+    };
+    let from = version_of(config)?;
+    if from > CONFIG_VERSION {
+        bail!("version: {from} is newer than {CONFIG_VERSION}, the latest this build supports");
+    }
+    if from == CONFIG_VERSION {
+        return Ok(Migrated { value, from });
+    }
+
+    for version in from..CONFIG_VERSION {
+        MIGRATIONS[(version - 1) as usize](config)
+            .with_context(|| format!("migrating config from version {version}"))?;
+    }
+
+    // keep `version` at the top, where people look for it
+    let mut migrated = Mapping::new();
+    migrated.insert("version".into(), CONFIG_VERSION.into());
+    for (key, value) in config.iter() {
+        if key.as_str() != Some("version") {
+            migrated.insert(key.clone(), value.clone());
+        }
+    }
+    Ok(Migrated {
+        value: Value::Mapping(migrated),
+        from,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{migrate, CONFIG_VERSION};
+
+    #[test]
+    fn test_migrate_unversioned() -> anyhow::Result<()> {
+        let v1 = serde_yaml::from_str("scrape_target: []\nspam:\n  enabled: false\n")?;
+        let migrated = migrate(v1)?;
+
+        assert_eq!(migrated.from, 1);
+        assert!(migrated.is_migrated());
+        let yaml = serde_yaml::to_string(&migrated.value)?;
+        assert!(yaml.starts_with(&format!("version: {CONFIG_VERSION}\n")));
+        assert!(yaml.contains("enabled: false"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate_current() -> anyhow::Result<()> {
+        let current = serde_yaml::from_str(&format!("version: {CONFIG_VERSION}\n"))?;
+        assert!(!migrate(current)?.is_migrated());
+
+        let newer = serde_yaml::from_str(&format!("version: {}\n", CONFIG_VERSION + 1))?;
+        assert!(migrate(newer).is_err());
+        assert!(migrate(serde_yaml::from_str("version: zero")?).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,335 @@
+use crate::config::{NotificationConfig, NotifyOn, WebhookTarget};
+use crate::events::{Event, EventKind};
+use backoff::ExponentialBackoffBuilder;
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, fs::OpenOptions, io::Write, time::Duration};
+
+lazy_static! {
+    static ref CLIENT: Client = Client::builder()
+        .user_agent("hacktoberfest.teknologiumum.com")
+        .timeout(Duration::from_secs(30))
+        .build()
+        .expect("Failed to build reqwest::Client");
+}
+
+/// A delivery that kept failing after every retry.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DeadLetter {
+    pub timestamp: DateTime<Utc>,
+    pub webhook: String,
+    pub event_id: u64,
+    pub body: String,
+    pub error: String,
+}
+
+pub fn notify_on(kind: &EventKind) -> Option<NotifyOn> {
+    match kind {
+        EventKind::IssueOpened { .. } => Some(NotifyOn::IssueOpened),
+        EventKind::PullRequestMerged { .. } => Some(NotifyOn::PullRequestMerged),
+        EventKind::GoalReached { .. } => Some(NotifyOn::GoalReached),
+        EventKind::ScrapeFailing { .. } => Some(NotifyOn::ScrapeFailing),
+        _ => None,
+    }
+}
+
+/// Human readable summary of an event, available to templates as `{{message}}`.
+pub fn message(kind: &EventKind) -> String {
+    match kind {
+        EventKind::IssueOpened {
+            repository,
+            html_url,
+            title,
+        } => format!("New hacktoberfest issue on {repository}: {title}\n{html_url}"),
+        EventKind::PullRequestOpened {
+            author,
+            html_url,
+            title,
+            ..
+        } => format!("{author} opened {title}\n{html_url}"),
+        EventKind::PullRequestMerged {
+            author,
+            html_url,
+            title,
+            ..
+        } => format!("{author} got {title} merged\n{html_url}"),
+        EventKind::ContributorCountChanged { before, after } => {
+            format!("Contributors: {before} -> {after}")
+        }
+        EventKind::ContributorRankChanged {
+            login,
+            before: Some(before),
+            after,
+        } => format!("{login} moved from #{before} to #{after}"),
+        EventKind::ContributorRankChanged {
+            login,
+            before: None,
+            after,
+        } => format!("{login} joined the leaderboard at #{after}"),
+        EventKind::GoalReached {
+            login,
+            merged_pulls,
+        } => format!("{login} reached the goal with {merged_pulls} merged pull requests"),
+        EventKind::ScrapeFailing { attempts, error } => {
+            format!("Scraping failed {attempts} times in a row: {error}")
+        }
+    }
+}
+
+/// Renders a webhook body. Every `{{key}}` in the template is replaced by the
+/// JSON-escaped value of that event field, or by the event `message`; without a
+/// template the event itself is sent.
+pub fn render(template: Option<&String>, event: &Event) -> Result<String, serde_json::Error> {
+    let mut fields = match serde_json::to_value(event)? {
+        serde_json::Value::Object(fields) => fields,
+        _ => serde_json::Map::new(),
+    };
+    fields.insert("message".into(), message(&event.kind).into());
+
+    let template = match template {
+        Some(template) => template,
+        None => return serde_json::to_string(&fields),
+    };
+
+    // a single pass, so a `{{key}}` inside a substituted value stays as it is
+    let mut body = String::with_capacity(template.len());
+    let mut rest = template.as_str();
+    while let Some(start) = rest.find("{{") {
+        body.push_str(&rest[..start]);
+        let tag = &rest[start..];
+        let Some(end) = tag.find("}}") else {
+            rest = tag;
+            break;
+        };
+        match fields.get(&tag[2..end]) {
+            // strip the quotes, templates put them where they want
+            Some(serde_json::Value::String(s)) => {
+                let escaped = serde_json::to_string(s)?;
+                body.push_str(&escaped[1..escaped.len() - 1]);
+            }
+            Some(other) => body.push_str(&other.to_string()),
+            None => body.push_str(&tag[..end + 2]),
+        }
+        rest = &tag[end + 2..];
+    }
+    body.push_str(rest);
+
+    // make sure the result is still JSON before sending it anywhere
+    serde_json::from_str::<serde_json::Value>(&body)?;
+    Ok(body)
+}
+
+async fn deliver(
+    webhook: &WebhookTarget,
+    body: &str,
+    max_elapsed: Duration,
+) -> Result<(), reqwest::Error> {
+    let backoff = ExponentialBackoffBuilder::new()
+        .with_max_elapsed_time(Some(max_elapsed))
+        .build();
+
+    backoff::future::retry(backoff, || async {
+        let mut request = CLIENT
+            .post(webhook.url.expose())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.to_owned());
+        for (name, value) in webhook.headers.iter() {
+            request = request.header(name, value.expose());
+        }
+
+        let response = request.send().await?;
+        match response.error_for_status() {
+            Ok(_) => Ok(()),
+            // retrying won't fix a bad request
+            Err(e)
+                if e.status().is_some_and(|status| {
+                    status.is_client_error() && status != StatusCode::TOO_MANY_REQUESTS
+                }) =>
+            {
+                Err(backoff::Error::permanent(e))
+            }
+            Err(e) => Err(backoff::Error::transient(e)),
+        }
+    })
+    .await
+}
+
+fn dead_letter(path: Option<&String>, letter: &DeadLetter) {
+    log::error!(
+        "webhook {} failed for event {}: {}",
+        letter.webhook,
+        letter.event_id,
+        letter.error
+    );
+
+    let path = match path {
+        Some(path) => path,
+        None => return,
+    };
+    let written = serde_json::to_string(letter)
+        .map_err(anyhow::Error::from)
+        .and_then(|line| {
+            let mut handle = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(handle, "{line}")?;
+            Ok(())
+        });
+    if let Err(e) = written {
+        log::error!("failed to write dead letter to {path}: {e}");
+    }
+}
+
+/// The events worth a notification, with what triggers them. The store records an
+/// issue or pull request once across scrapes, but a repository scraped for two
+/// targets announces its issues twice in the same scrape, those are sent once.
+fn notifiable(events: &[Event]) -> Vec<(&Event, NotifyOn)> {
+    let mut subjects = HashSet::new();
+    events
+        .iter()
+        .filter(|event| match event.kind.subject() {
+            Some(subject) => subjects.insert(subject),
+            None => true,
+        })
+        .filter_map(|event| Some((event, notify_on(&event.kind)?)))
+        .collect()
+}
+
+/// Sends every notable event to the webhooks subscribed to it, in the background.
+pub fn dispatch(config: &NotificationConfig, events: &[Event]) {
+    for (event, trigger) in notifiable(events) {
+        for webhook in config
+            .webhooks
+            .iter()
+            .filter(|webhook| webhook.notify_on.is_empty() || webhook.notify_on.contains(&trigger))
+        {
+            let webhook = webhook.clone();
+            let event = event.clone();
+            let dead_letter_path = config.dead_letter_path.clone();
+            let max_elapsed = Duration::from_secs(config.retry_max_elapsed_secs);
+
+            tokio::spawn(async move {
+                let result = match render(webhook.template.as_ref(), &event) {
+                    Ok(body) => deliver(&webhook, &body, max_elapsed)
+                        .await
+                        // the URL may embed credentials
+                        .map_err(|e| (body, e.without_url().to_string())),
+                    Err(e) => Err((String::new(), format!("invalid template: {e}"))),
+                };
+
+                if let Err((body, error)) = result {
+                    dead_letter(
+                        dead_letter_path.as_ref(),
+                        &DeadLetter {
+                            timestamp: Utc::now(),
+                            webhook: webhook.name.clone(),
+                            event_id: event.id,
+                            body,
+                            error,
+                        },
+                    );
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::config::NotifyOn;
+    use crate::events::{Event, EventKind};
+
+    use super::{notifiable, render};
+
+    fn event() -> Event {
+        Event {
+            id: 7,
+            timestamp: Utc::now(),
+            kind: EventKind::GoalReached {
+                login: "some\"body".to_owned(),
+                merged_pulls: 4,
+            },
+        }
+    }
+
+    #[test]
+    fn test_render_template() -> anyhow::Result<()> {
+        let template = r#"{"content": "{{message}}", "count": {{merged_pulls}}, "id": {{id}}}"#;
+        let body = render(Some(&template.to_owned()), &event())?;
+        let json: serde_json::Value = serde_json::from_str(&body)?;
+
+        assert_eq!(
+            json["content"],
+            "some\"body reached the goal with 4 merged pull requests"
+        );
+        assert_eq!(json["count"], 4);
+        assert_eq!(json["id"], 7);
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_without_template() -> anyhow::Result<()> {
+        let body = render(None, &event())?;
+        let json: serde_json::Value = serde_json::from_str(&body)?;
+        assert_eq!(json["type"], "GoalReached");
+        assert_eq!(json["login"], "some\"body");
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_substituted_once() -> anyhow::Result<()> {
+        let event = Event {
+            id: 7,
+            timestamp: Utc::now(),
+            kind: EventKind::IssueOpened {
+                repository: "teknologi-umum/pehape".to_owned(),
+                html_url: "https://github.com/teknologi-umum/pehape/issues/1".to_owned(),
+                title: "{{repository}}".to_owned(),
+            },
+        };
+        let template = r#"{"title": "{{title}}", "other": "{{unknown}}"}"#.to_owned();
+        let json: serde_json::Value = serde_json::from_str(&render(Some(&template), &event)?)?;
+        assert_eq!(json["title"], "{{repository}}");
+        assert_eq!(json["other"], "{{unknown}}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_unterminated_tag() -> anyhow::Result<()> {
+        let template = r#"{"id": {{id}}, "content": "left {{open"}"#.to_owned();
+        let json: serde_json::Value = serde_json::from_str(&render(Some(&template), &event())?)?;
+        assert_eq!(json["id"], 7);
+        assert_eq!(json["content"], "left {{open");
+        Ok(())
+    }
+
+    #[test]
+    fn test_notifiable() {
+        let issue = |id: u64| Event {
+            id,
+            timestamp: Utc::now(),
+            kind: EventKind::IssueOpened {
+                repository: "teknologi-umum/pehape".to_owned(),
+                html_url: "https://github.com/teknologi-umum/pehape/issues/1".to_owned(),
+                title: "Add tests".to_owned(),
+            },
+        };
+        let events = [issue(1), event(), issue(2)];
+        let triggers: Vec<(u64, NotifyOn)> = notifiable(&events)
+            .into_iter()
+            .map(|(event, trigger)| (event.id, trigger))
+            .collect();
+        assert_eq!(
+            triggers,
+            vec![(1, NotifyOn::IssueOpened), (7, NotifyOn::GoalReached)]
+        );
+    }
+
+    #[test]
+    fn test_render_invalid_template() {
+        let template = r#"{"content": {{message}}}"#.to_owned();
+        assert!(render(Some(&template), &event()).is_err());
+    }
+}
@@ -0,0 +1,96 @@
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name: OsString = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+pub fn tmp_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".tmp")
+}
+
+/// `path.n`, the n-th most recent backup of `path`.
+pub fn numbered(path: &Path, n: usize) -> PathBuf {
+    with_suffix(path, &format!(".{n}"))
+}
+
+/// Renames `from` to `to`, then fsyncs the parent directory so the rename itself
+/// survives a crash.
+pub fn rename_durable(from: &Path, to: &Path) -> io::Result<()> {
+    fs::rename(from, to)?;
+    let parent = match to.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    File::open(parent)?.sync_all()
+}
+
+/// Replaces `path` with `contents` through a fsynced temporary sibling, so after a
+/// crash at any point the file holds either the old or the new contents.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let tmp = tmp_path(path);
+    {
+        let mut handle = File::create(&tmp)?;
+        handle.write_all(contents)?;
+        handle.sync_all()?;
+    }
+    rename_durable(&tmp, path)
+}
+
+/// Shifts `path.1` .. `path.{keep - 1}` one slot up, dropping the oldest backup,
+/// so `path.1` is free for a new one.
+pub fn rotate(path: &Path, keep: usize) -> io::Result<()> {
+    for n in (1..keep).rev() {
+        let from = numbered(path, n);
+        if from.exists() {
+            fs::rename(&from, numbered(path, n + 1))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf, process};
+
+    use super::{numbered, rotate, tmp_path, write_atomic};
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("hacktoberfest-{}-{name}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_write_atomic() -> anyhow::Result<()> {
+        let path = scratch_dir("write_atomic").join("target.yml");
+        write_atomic(&path, b"old")?;
+        write_atomic(&path, b"new")?;
+
+        assert_eq!(fs::read_to_string(&path)?, "new");
+        assert!(!tmp_path(&path).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rotate() -> anyhow::Result<()> {
+        let path = scratch_dir("rotate").join("data.db");
+        for generation in 0..5 {
+            rotate(&path, 3)?;
+            fs::write(numbered(&path, 1), generation.to_string())?;
+        }
+
+        assert_eq!(fs::read_to_string(numbered(&path, 1))?, "4");
+        assert_eq!(fs::read_to_string(numbered(&path, 2))?, "3");
+        assert_eq!(fs::read_to_string(numbered(&path, 3))?, "2");
+        assert!(!numbered(&path, 4).exists());
+        Ok(())
+    }
+}
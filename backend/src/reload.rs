@@ -0,0 +1,231 @@
+use crate::config::{Config, ScrapeTarget};
+use crate::lock::LockRecover;
+use crate::RRunContext;
+use notify::{RecursiveMode, Watcher};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::mpsc;
+
+/// Editors and atomic writes touch the file several times per save.
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+fn describe_target(target: &ScrapeTarget) -> String {
+    match &target.repo_names {
+        Some(repo_names) => format!("{} {:?}", target.username, repo_names),
+        None => target.username.clone(),
+    }
+}
+
+fn flatten(prefix: String, value: &Value, leaves: &mut BTreeMap<String, Value>) {
+    let join = |key: &dyn ToString| {
+        if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{prefix}.{}", key.to_string())
+        }
+    };
+    match value {
+        Value::Object(fields) => {
+            for (key, value) in fields.iter() {
+                flatten(join(key), value, leaves);
+            }
+        }
+        Value::Array(items) => {
+            for (i, value) in items.iter().enumerate() {
+                flatten(join(&i), value, leaves);
+            }
+        }
+        leaf => {
+            leaves.insert(prefix, leaf.clone());
+        }
+    }
+}
+
+/// Human readable changes between two configs. Targets are listed as added or
+/// removed, every other setting by its path. Webhook, admin and GitHub token values
+/// may hold credentials, so only their paths are shown.
+pub fn diff(previous: &Config, next: &Config) -> Vec<String> {
+    let mut changes = Vec::new();
+
+    for target in next.scrape_target.iter() {
+        if !previous.scrape_target.contains(target) {
+            changes.push(format!("+ scrape_target {}", describe_target(target)));
+        }
+    }
+    for target in previous.scrape_target.iter() {
+        if !next.scrape_target.contains(target) {
+            changes.push(format!("- scrape_target {}", describe_target(target)));
+        }
+    }
+
+    let leaves = |config: &Config| {
+        let mut leaves = BTreeMap::new();
+        if let Ok(Value::Object(mut fields)) = serde_json::to_value(config) {
+            fields.remove("scrape_target");
+            flatten(String::new(), &Value::Object(fields), &mut leaves);
+        }
+        leaves
+    };
+    let (before, after) = (leaves(previous), leaves(next));
+    let mut paths: Vec<&String> = before.keys().chain(after.keys()).collect();
+    paths.sort();
+    paths.dedup();
+    for path in paths {
+        let (old, new) = (before.get(path), after.get(path));
+        if old == new {
+            continue;
+        }
+        let show = |value: Option<&Value>| match value {
+            Some(_)
+                if path.starts_with("notifications.webhooks.")
+                    || path.starts_with("admin.")
+                    || path == "server.github_token" =>
+            {
+                "<redacted>".to_owned()
+            }
+            Some(value) => value.to_string(),
+            None => "<unset>".to_owned(),
+        };
+        let note = if path.starts_with("server.") || path.starts_with("cors.") {
+            " (after a restart)"
+        } else {
+            ""
+        };
+        changes.push(format!("~ {path}: {} -> {}{note}", show(old), show(new)));
+    }
+
+    changes
+}
+
+/// Re-reads the config file and swaps it in, with the admin API's target changes
+/// on top. An invalid file is rejected and the running config kept. Returns the
+/// applied changes.
+pub fn reload(ctx: &RRunContext<'_>) -> anyhow::Result<Vec<String>> {
+    let (config_path, store) = {
+        let g_ctx = ctx.lock_recover();
+        (g_ctx.config_path.clone(), g_ctx.store.clone())
+    };
+    let next = Config::from_file(&config_path)?.with_target_overrides(store.target_overrides()?)?;
+
+    let g_ctx = ctx.lock_recover();
+    let changes = diff(&g_ctx.config.borrow(), &next);
+    *g_ctx.config.borrow_mut() = next;
+    Ok(changes)
+}
+
+fn reload_and_log(ctx: &RRunContext<'_>, reason: &str) {
+    match reload(ctx) {
+        Ok(changes) if changes.is_empty() => println!("config reloaded ({reason}), no changes"),
+        Ok(changes) => {
+            println!("config reloaded ({reason}):");
+            for change in changes.iter() {
+                println!("  {change}");
+            }
+        }
+        Err(e) => log::error!("config reload ({reason}) rejected, keeping the previous one: {e:#}"),
+    }
+}
+
+/// Reloads the config whenever its file changes or the process receives SIGHUP.
+pub async fn watch(ctx: RRunContext<'static>) -> anyhow::Result<()> {
+    let config_path = PathBuf::from(ctx.lock_recover().config_path.clone());
+    let file_name = config_path.file_name().map(ToOwned::to_owned);
+    // watch the directory, atomic saves replace the file and its inode
+    let dir = match config_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => Path::new(".").to_path_buf(),
+    };
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut watcher =
+        notify::recommended_watcher(move |res: notify::Result<notify::Event>| match res {
+            Ok(event) if event.kind.is_modify() || event.kind.is_create() => {
+                if event
+                    .paths
+                    .iter()
+                    .any(|path| path.file_name() == file_name.as_deref())
+                {
+                    let _ = tx.send(());
+                }
+            }
+            Ok(_) => {}
+            Err(e) => log::error!("config watcher error: {e}"),
+        })?;
+    watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+
+    let mut hangup = signal(SignalKind::hangup())?;
+    loop {
+        tokio::select! {
+            Some(()) = rx.recv() => {
+                tokio::time::sleep(DEBOUNCE).await;
+                while rx.try_recv().is_ok() {}
+                reload_and_log(&ctx, "file changed");
+            }
+            Some(()) = hangup.recv() => reload_and_log(&ctx, "SIGHUP"),
+            else => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::{env, fs, process};
+
+    use crate::config::{Config, ScrapeTarget, WebhookTarget};
+    use crate::secret::Secret;
+    use crate::RunContext;
+
+    use super::{diff, reload};
+
+    #[test]
+    fn test_diff() {
+        let previous = Config::default();
+        let mut next = Config::default();
+        next.scrape_target
+            .push(ScrapeTarget::user("somebody".to_owned()));
+        next.spam.tiny_diff_max_lines = 5;
+        next.notifications.webhooks.push(WebhookTarget {
+            name: "discord".to_owned(),
+            url: Secret::new("https://discord.com/api/webhooks/secret"),
+            headers: Default::default(),
+            notify_on: vec![],
+            template: None,
+        });
+
+        let changes = diff(&previous, &next);
+        assert_eq!(changes[0], "+ scrape_target somebody");
+        assert!(changes.contains(&"~ spam.tiny_diff_max_lines: 0 -> 5".to_owned()));
+        assert!(changes.iter().all(|change| !change.contains("secret")));
+        assert!(diff(&next, &next).is_empty());
+    }
+
+    #[test]
+    fn test_reload_keeps_previous_on_error() -> anyhow::Result<()> {
+        let path = env::temp_dir()
+            .join(format!("hacktoberfest-{}-reload.yml", process::id()))
+            .to_string_lossy()
+            .into_owned();
+        let mut ctx = RunContext::default();
+        ctx.config_path = path.clone();
+        let ctx = Arc::new(Mutex::new(ctx));
+
+        fs::write(&path, "scrape_target: [")?;
+        assert!(reload(&ctx).is_err());
+        assert_eq!(
+            ctx.lock().unwrap().config.borrow().scrape_target,
+            Config::default().scrape_target
+        );
+
+        fs::write(&path, "scrape_target: []")?;
+        let changes = reload(&ctx)?;
+        assert_eq!(changes, vec!["- scrape_target teknologi-umum".to_owned()]);
+        assert!(ctx.lock().unwrap().config.borrow().scrape_target.is_empty());
+
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+}
@@ -1,18 +1,24 @@
-use crate::config::ScrapeTargetType;
-use crate::github::{Github, Issue, Repository, User};
+use crate::config::{ScrapeTargetType, SpamPolicy};
+use crate::events::{self, EventKind, Snapshot};
+use crate::github::{Github, Issue, Repository, Review, User};
 use crate::github::{GithubError, PullRequest};
 use crate::handlers::{SCRAPE_COUNT_TOTAL, SCRAPE_HISTOGRAM_DUR_SECONDS, SCRAPE_REPO_COUNT_TOTAL};
+use crate::lock::LockRecover;
+use crate::notifier;
+use crate::spam::{self, SpamReason};
+use crate::store::StoreError;
 use crate::{RunContext, FIRST_OCTOBER, LAST_OCTOBER};
-use chrono::prelude::Local;
 use chrono::{DateTime, Utc};
 use log::trace;
+use schemars::JsonSchema;
 use scopeguard::defer;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct RepositoryCollection {
     pub full_name: String,
     pub html_url: String,
@@ -24,30 +30,50 @@ pub struct RepositoryCollection {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub issues: Vec<Issue>,
+    #[serde(default)]
+    pub health: RepositoryHealth,
 }
 
-#[derive(Serialize, Deserialize)]
+/// Repository activity within the event window, derived from its pull requests.
+/// Durations are in seconds.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq, JsonSchema)]
+pub struct RepositoryHealth {
+    pub opened_pulls: i64,
+    pub merged_pulls: i64,
+    pub closed_unmerged_pulls: i64,
+    pub open_pulls: i64,
+    pub median_merge_secs: Option<i64>,
+    /// First review by someone other than the author, or closing, whichever came first.
+    pub median_first_response_secs: Option<i64>,
+    pub open_backlog_median_age_secs: Option<i64>,
+    pub open_backlog_oldest_age_secs: Option<i64>,
+    pub open_hacktoberfest_issues: i64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct ContributorCollection {
     pub full_name: String,
     pub profile_url: String,
+    #[serde(default)]
+    pub avatar_url: String,
     pub merged_pulls: i64,
     pub pending_pulls: i64,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 pub enum PullRequestState {
     Open,
     Closed,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub enum PullRequestMergeableState {
     Unknown,
     Dirty,
     Clean,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub enum PullRequestAuthorAssociation {
     FirstTimeContributor,
     Contributor,
@@ -56,15 +82,49 @@ pub enum PullRequestAuthorAssociation {
     Unknown,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct PullRequestDiff {
     pub additions: i64,
     pub deletions: i64,
     pub changed_files: i64,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct MaintainerCollection {
+    pub full_name: String,
+    pub profile_url: String,
+    pub avatar_url: String,
+    pub reviews_submitted: i64,
+    pub merged_pulls: i64,
+    /// Median of seconds between a pull request being opened and this maintainer
+    /// submitting its first review, `None` if they never reviewed first.
+    pub median_first_review_secs: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct PullRequestReview {
+    pub user: User,
+    pub state: String,
+    pub submitted_at: DateTime<Utc>,
+}
+
+/// Whether a pull request counts toward the event goal.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, JsonSchema)]
+pub enum PullRequestEligibility {
+    /// Merged within the event window.
+    Accepted,
+    /// Still open, might be accepted later.
+    Pending,
+    /// Closed without merge, or merged outside of the event window.
+    NotAccepted,
+    /// Flagged as spam, see `PullRequestCollection::spam_reasons`.
+    Flagged,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct PullRequestCollection {
+    #[serde(default)]
+    pub repository: String,
     pub number: i64,
     pub html_url: String,
     pub title: String,
@@ -77,19 +137,78 @@ pub struct PullRequestCollection {
     pub merged_at: DateTime<Utc>,
     pub closed_at: DateTime<Utc>,
     pub merged: bool,
+    #[serde(default)]
+    pub merged_by: Option<User>,
     pub draft: bool,
     pub requested_reviewers: Vec<User>,
     pub author_association: PullRequestAuthorAssociation,
     pub comments: i64,
     pub review_comments: i64,
     pub diff: PullRequestDiff,
+    #[serde(default)]
+    pub reviews: Vec<PullRequestReview>,
+    #[serde(default)]
+    pub spam_reasons: Vec<SpamReason>,
+    #[serde(default)]
+    pub excluded_from_scoring: bool,
+}
+
+impl PullRequestCollection {
+    pub fn eligibility(&self) -> PullRequestEligibility {
+        if self.excluded_from_scoring {
+            PullRequestEligibility::Flagged
+        } else if self.merged_at.gt(&FIRST_OCTOBER) && self.merged_at.lt(&LAST_OCTOBER) {
+            PullRequestEligibility::Accepted
+        } else if self.state == PullRequestState::Open {
+            PullRequestEligibility::Pending
+        } else {
+            PullRequestEligibility::NotAccepted
+        }
+    }
+
+    pub fn is_merged(&self) -> bool {
+        self.merged_at.ne(&DateTime::<Utc>::MIN_UTC)
+    }
+
+    /// Earliest review submitted by someone other than the author.
+    pub fn first_review(&self) -> Option<&PullRequestReview> {
+        self.reviews
+            .iter()
+            .filter(|review| review.user.login != self.user.login)
+            .min_by_key(|review| review.submitted_at)
+    }
+}
+
+/// Median of `values`, averaging the two middle values for even-sized inputs.
+pub fn median(mut values: Vec<i64>) -> Option<i64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_unstable();
+    let lower = values[(values.len() - 1) / 2];
+    let upper = values[values.len() / 2];
+    Some((lower + upper) / 2)
 }
 
 #[derive(Debug)]
 pub enum ScrapeError {
     InvalidRepo,
     Github(GithubError),
-    Serde(serde_json::Error),
+    Store(StoreError),
+}
+
+impl ScrapeError {
+    /// What kind of failure this is, short enough to publish, unlike the error
+    /// itself, which may carry URLs and response bodies.
+    pub fn category(&self) -> &'static str {
+        match self {
+            Self::InvalidRepo => "invalid_repository",
+            Self::Github(GithubError::App(_)) => "github_api",
+            Self::Github(GithubError::StatusCode(_)) => "github_status",
+            Self::Github(GithubError::Request(_)) => "github_unreachable",
+            Self::Store(_) => "store",
+        }
+    }
 }
 
 pub async fn run_scrape<'a, B>(ctx: &'a crate::RRunContext<'_>, backoff: B, github_client: &Github)
@@ -103,22 +222,53 @@ where
 
     let scrape_interval;
     {
-        let _ctx = ctx.lock().unwrap();
+        let _ctx = ctx.lock_recover();
         scrape_interval = _ctx.scrape_interval;
         drop(_ctx);
     }
 
+    let mut consecutive_failures: u32 = 0;
     loop {
-        let _ = backoff::future::retry_notify(
+        let scraped = backoff::future::retry_notify(
             backoff.clone(),
             || async { Ok(scrape(ctx, github_client).await?) },
-            |err, dur| println!("scrape error {:?}: {:?}", dur, err),
+            |err, dur| {
+                println!("scrape error {:?}: {:?}", dur, err);
+                consecutive_failures += 1;
+                notify_scrape_failing(ctx, consecutive_failures, &err);
+            },
         )
         .await;
+        if scraped.is_ok() {
+            consecutive_failures = 0;
+        }
         tokio::time::sleep(Duration::from_secs(scrape_interval)).await;
     }
 }
 
+/// Records and notifies a `ScrapeFailing` event once the failure streak reaches the
+/// configured threshold.
+fn notify_scrape_failing(ctx: &crate::RRunContext<'_>, attempts: u32, err: &ScrapeError) {
+    let (store, notifications) = {
+        let g_ctx = ctx.lock_recover();
+        let notifications = g_ctx.config.borrow().notifications.clone();
+        (g_ctx.store.clone(), notifications)
+    };
+    if attempts != notifications.scrape_failure_threshold {
+        return;
+    }
+
+    log::error!("scraping failed {attempts} times in a row: {:?}", err);
+    let change = EventKind::ScrapeFailing {
+        attempts,
+        error: err.category().to_owned(),
+    };
+    match store.record_events(vec![change], Utc::now()) {
+        Ok(recorded) => notifier::dispatch(&notifications, &recorded),
+        Err(e) => log::error!("failed to record scrape failure: {:?}", e),
+    }
+}
+
 pub async fn scrape_repository_collection(
     github_client: &Github,
     username: &String,
@@ -155,40 +305,168 @@ pub async fn scrape_repository_collection(
         created_at: repo.created_at,
         updated_at: repo.updated_at,
         issues,
+        health: RepositoryHealth::default(),
     })
 }
 
+pub fn scrape_repository_health(
+    pulls: &[PullRequestCollection],
+    open_issues: usize,
+    now: DateTime<Utc>,
+) -> RepositoryHealth {
+    let count = |predicate: &dyn Fn(&PullRequestCollection) -> bool| -> i64 {
+        pulls.iter().filter(|pull| predicate(pull)).count() as i64
+    };
+    let secs_between =
+        |from: DateTime<Utc>, to: DateTime<Utc>| to.signed_duration_since(from).num_seconds();
+
+    let merge_secs: Vec<i64> = pulls
+        .iter()
+        .filter(|pull| pull.is_merged())
+        .map(|pull| secs_between(pull.created_at, pull.merged_at))
+        .collect();
+    let first_response_secs: Vec<i64> = pulls
+        .iter()
+        .filter_map(|pull| {
+            let reviewed_at = pull.first_review().map(|review| review.submitted_at);
+            let closed_at = match pull.state {
+                PullRequestState::Closed => Some(pull.closed_at),
+                PullRequestState::Open => None,
+            };
+            [reviewed_at, closed_at]
+                .into_iter()
+                .flatten()
+                .min()
+                .map(|responded_at| secs_between(pull.created_at, responded_at))
+        })
+        .collect();
+    let open_ages: Vec<i64> = pulls
+        .iter()
+        .filter(|pull| pull.state == PullRequestState::Open)
+        .map(|pull| secs_between(pull.created_at, now))
+        .collect();
+
+    RepositoryHealth {
+        opened_pulls: pulls.len() as i64,
+        merged_pulls: count(&|pull| pull.is_merged()),
+        closed_unmerged_pulls: count(&|pull| {
+            pull.state == PullRequestState::Closed && !pull.is_merged()
+        }),
+        open_pulls: open_ages.len() as i64,
+        median_merge_secs: median(merge_secs),
+        median_first_response_secs: median(first_response_secs),
+        open_backlog_oldest_age_secs: open_ages.iter().max().copied(),
+        open_backlog_median_age_secs: median(open_ages),
+        open_hacktoberfest_issues: open_issues as i64,
+    }
+}
+
+/// Merged pull requests of each contributor, every other pull request of theirs is
+/// pending, closed ones included. `unscraped` are the listed pull requests whose
+/// details failed to load, they're counted from the listing alone.
 pub async fn scrape_contributor_collection(
-    pulls: &[PullRequest],
+    pulls: &[PullRequestCollection],
+    unscraped: &[PullRequest],
 ) -> Result<Vec<ContributorCollection>, ScrapeError> {
-    let contributors: Vec<ContributorCollection> = pulls
+    let mut contributors_map = HashMap::<String, ContributorCollection>::new();
+    let mut count = |user: &User, merged: bool| {
+        let contributor = contributors_map
+            .entry(user.login.clone())
+            .or_insert_with(|| ContributorCollection {
+                full_name: user.login.clone(),
+                profile_url: user.html_url.clone(),
+                avatar_url: user.avatar_url.clone(),
+                merged_pulls: 0,
+                pending_pulls: 0,
+            });
+        if merged {
+            contributor.merged_pulls += 1
+        } else {
+            contributor.pending_pulls += 1
+        }
+    };
+
+    for pull in pulls
         .iter()
         .filter(|pull| pull.created_at.ge(&FIRST_OCTOBER) && pull.created_at.le(&LAST_OCTOBER))
-        .fold(HashMap::<String, ContributorCollection>::new(), |mut contributors_map, pull| {
-            let merged: bool = matches!(pull.merged_at, Some(date) if date.gt(&FIRST_OCTOBER) && date.lt(&LAST_OCTOBER));
-            match contributors_map.get_mut(&pull.user.login) {
-                Some(contributor) => {
-                    if merged { contributor.merged_pulls += 1 }
-                    if !merged { contributor.pending_pulls += 1 }
-                }
-                _ => {
-                    contributors_map.insert(
-                        pull.user.login.clone(),
-                        ContributorCollection {
-                            full_name: pull.user.login.clone(),
-                            profile_url: pull.user.html_url.clone(),
-                            pending_pulls: (!merged).into(),
-                            merged_pulls: merged.into(),
-                        },
-                    );
-                }
-            };
-            contributors_map
-        })
+    {
+        match pull.eligibility() {
+            PullRequestEligibility::Accepted => count(&pull.user, true),
+            PullRequestEligibility::Pending | PullRequestEligibility::NotAccepted => {
+                count(&pull.user, false)
+            }
+            PullRequestEligibility::Flagged => {}
+        }
+    }
+    for pull in unscraped
+        .iter()
+        .filter(|pull| pull.created_at.ge(&FIRST_OCTOBER) && pull.created_at.le(&LAST_OCTOBER))
+    {
+        let merged = matches!(pull.merged_at, Some(date) if date.gt(&FIRST_OCTOBER) && date.lt(&LAST_OCTOBER));
+        count(&pull.user, merged);
+    }
+
+    Ok(contributors_map.into_values().collect())
+}
+
+pub async fn scrape_maintainer_collection(
+    pulls: &[PullRequestCollection],
+) -> Result<Vec<MaintainerCollection>, ScrapeError> {
+    fn entry<'m>(
+        maintainers_map: &'m mut HashMap<String, (MaintainerCollection, Vec<i64>)>,
+        user: &User,
+    ) -> &'m mut (MaintainerCollection, Vec<i64>) {
+        maintainers_map
+            .entry(user.login.clone())
+            .or_insert_with(|| {
+                (
+                    MaintainerCollection {
+                        full_name: user.login.clone(),
+                        profile_url: user.html_url.clone(),
+                        avatar_url: user.avatar_url.clone(),
+                        reviews_submitted: 0,
+                        merged_pulls: 0,
+                        median_first_review_secs: None,
+                    },
+                    Vec::new(),
+                )
+            })
+    }
+
+    let mut maintainers_map = HashMap::<String, (MaintainerCollection, Vec<i64>)>::new();
+    for pull in pulls.iter() {
+        // self-reviews are not maintainer activity
+        for review in pull
+            .reviews
+            .iter()
+            .filter(|review| review.user.login != pull.user.login)
+        {
+            entry(&mut maintainers_map, &review.user)
+                .0
+                .reviews_submitted += 1;
+        }
+
+        if let Some(review) = pull.first_review() {
+            let wait = review.submitted_at.signed_duration_since(pull.created_at);
+            entry(&mut maintainers_map, &review.user)
+                .1
+                .push(wait.num_seconds());
+        }
+
+        if let (true, Some(merged_by)) = (pull.merged, &pull.merged_by) {
+            entry(&mut maintainers_map, merged_by).0.merged_pulls += 1;
+        }
+    }
+
+    let maintainers = maintainers_map
         .into_values()
+        .map(|(mut maintainer, first_review_secs)| {
+            maintainer.median_first_review_secs = median(first_review_secs);
+            maintainer
+        })
         .collect();
 
-    Ok(contributors)
+    Ok(maintainers)
 }
 
 pub async fn scrape_pull_request(
@@ -196,13 +474,27 @@ pub async fn scrape_pull_request(
     username: &String,
     repo: &Repository,
     number: i64,
+    spam_policy: &SpamPolicy,
 ) -> Result<PullRequestCollection, ScrapeError> {
     let pr: PullRequest = github_client
         .pull_request(username, &repo.name, number)
         .await
         .map_err(ScrapeError::Github)?;
 
-    let pull_request = PullRequestCollection {
+    // the pull request counts without its reviews, they only feed maintainer stats
+    let reviews: Vec<Review> = match github_client
+        .list_reviews(username, &repo.name, number)
+        .await
+    {
+        Ok(reviews) => reviews,
+        Err(e) => {
+            log::warn!("err {:?} -> reviews of {}, left out", e, pr.html_url);
+            Vec::new()
+        }
+    };
+
+    let mut pull_request = PullRequestCollection {
+        repository: repo.full_name.clone(),
         number: pr.number,
         html_url: pr.html_url,
         title: pr.title,
@@ -225,6 +517,7 @@ pub async fn scrape_pull_request(
         merged_at: pr.merged_at.unwrap_or(DateTime::<Utc>::MIN_UTC),
         closed_at: pr.closed_at.unwrap_or(DateTime::<Utc>::MIN_UTC),
         merged: pr.merged.unwrap_or(false),
+        merged_by: pr.merged_by,
         draft: pr.draft.unwrap_or(false),
         requested_reviewers: match pr.requested_reviewers {
             Some(requested_reviewers) => requested_reviewers,
@@ -247,8 +540,53 @@ pub async fn scrape_pull_request(
             deletions: pr.deletions.unwrap_or(0),
             changed_files: pr.changed_files.unwrap_or(0),
         },
+        // pending reviews have no submission date and ghost users have no account
+        reviews: reviews
+            .into_iter()
+            .filter_map(|review| match (review.user, review.submitted_at) {
+                (Some(user), Some(submitted_at)) => Some(PullRequestReview {
+                    user,
+                    state: review.state,
+                    submitted_at,
+                }),
+                _ => None,
+            })
+            .collect(),
+        spam_reasons: Vec::new(),
+        excluded_from_scoring: false,
     };
 
+    if spam_policy.enabled {
+        // without the files there is nothing to judge, the pull request still counts
+        let files = match github_client
+            .list_pull_request_files(username, &repo.name, number)
+            .await
+        {
+            Ok(files) => files,
+            Err(e) => {
+                log::warn!(
+                    "err {:?} -> files of {}, spam checks skipped",
+                    e,
+                    pull_request.html_url
+                );
+                return Ok(pull_request);
+            }
+        };
+        let closed_by = if spam::is_quick_close_candidate(&pull_request, spam_policy) {
+            match github_client.issue(username, &repo.name, number).await {
+                Ok(issue) => issue.closed_by,
+                Err(e) => {
+                    log::debug!("err {:?} -> closer of {}", e, pull_request.html_url);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        pull_request.spam_reasons =
+            spam::detect_pull_request(&pull_request, &files, closed_by.as_ref(), spam_policy);
+    }
+
     Ok(pull_request)
 }
 
@@ -261,13 +599,59 @@ pub async fn scrape<'a>(
         println!("scraper stop");
     }
 
+    let started_at = Utc::now();
+    let (store, notifications, backup_path, backup_count) = {
+        let g_ctx = ctx.lock_recover();
+        let notifications = g_ctx.config.borrow().notifications.clone();
+        (
+            g_ctx.store.clone(),
+            notifications,
+            Path::new(&g_ctx.state_dir).join("backups").join("data.db"),
+            g_ctx.backup_count,
+        )
+    };
+
+    let (snapshot, maintainer_collection) = match scrape_snapshot(ctx, github_client).await {
+        Ok(scraped) => scraped,
+        Err(e) => {
+            if let Err(store_err) = store.record_failed_scrape(started_at, &format!("{:?}", e)) {
+                log::error!("failed to record scrape failure: {:?}", store_err);
+            }
+            return Err(e);
+        }
+    };
+
+    // nothing to compare against on the very first scrape
+    let changes = match store.snapshot().map_err(ScrapeError::Store)? {
+        Some(previous) => events::diff(&previous, &snapshot),
+        None => Vec::new(),
+    };
+    let recorded = store
+        .save_scrape(started_at, &snapshot, &maintainer_collection, changes)
+        .map_err(ScrapeError::Store)?;
+    notifier::dispatch(&notifications, &recorded);
+
+    // the scrape is already committed, a failed backup shouldn't fail it
+    if let Err(e) = store.backup(&backup_path, backup_count) {
+        log::error!("failed to back up store to {:?}: {}", backup_path, e);
+    }
+
+    Ok(())
+}
+
+/// Scrapes every target, without persisting anything.
+pub async fn scrape_snapshot<'a>(
+    ctx: &Arc<Mutex<RunContext<'a>>>,
+    github_client: &Github,
+) -> Result<(Snapshot, Vec<MaintainerCollection>), ScrapeError> {
     SCRAPE_COUNT_TOTAL.with_label_values(&[]).inc();
 
-    let scrape_targets = { ctx.lock().unwrap().config.borrow().scrape_target.clone() };
-    let scrape_per_page_limit = { ctx.lock().unwrap().scrape_per_page };
+    let scrape_targets = { ctx.lock_recover().config.borrow().scrape_target.clone() };
+    let scrape_per_page_limit = { ctx.lock_recover().scrape_per_page };
+    let spam_policy = { ctx.lock_recover().config.borrow().spam.clone() };
     let mut repository_collection: Vec<RepositoryCollection> = Vec::new();
-    let mut contributor_map: HashMap<String, ContributorCollection> = HashMap::new();
     let mut pull_request_collection: Vec<PullRequestCollection> = Vec::new();
+    let mut unscraped_pulls: Vec<PullRequest> = Vec::new();
 
     for target in scrape_targets.into_iter().filter(|t| !t.ignore) {
         let username = &target.username;
@@ -292,11 +676,16 @@ pub async fn scrape<'a>(
                 .collect();
         }
 
+        let now = Utc::now();
         for repo in repository.iter() {
             // Skip if there isn't any "hacktoberfest" topic on the repository
             if !repo.topics.contains(&"hacktoberfest".into()) {
                 continue;
             }
+            if !target.filter.matches(repo, now) {
+                trace!("{} filtered out by scrape_target filter", repo.full_name);
+                continue;
+            }
             SCRAPE_REPO_COUNT_TOTAL
                 .with_label_values(&[username, &repo.name])
                 .inc();
@@ -308,76 +697,318 @@ pub async fn scrape<'a>(
                     m_dur.stop_and_record();
                 }
 
-                match scrape_repository_collection(github_client, &username, repo).await {
-                    Ok(collection) => repository_collection.push(collection),
-                    Err(ScrapeError::InvalidRepo) => trace!("ignoring {}", repo.full_name),
-                    Err(e) => log::debug!("err {:?} -> {:?}", e, repo),
-                };
+                let collection =
+                    match scrape_repository_collection(github_client, username, repo).await {
+                        Ok(collection) => Some(collection),
+                        Err(ScrapeError::InvalidRepo) => {
+                            trace!("ignoring {}", repo.full_name);
+                            None
+                        }
+                        Err(e) => {
+                            log::debug!("err {:?} -> {:?}", e, repo);
+                            None
+                        }
+                    };
 
                 let pulls: Vec<PullRequest> = github_client
                     .list_pull_request(&username, &repo.name)
                     .await
                     .map_err(ScrapeError::Github)?;
 
-                match scrape_contributor_collection(&pulls).await {
-                    Ok(collections) => {
-                        for contributor in collections.iter() {
-                            match contributor_map.get_mut(&contributor.full_name) {
-                                Some(c) => {
-                                    c.merged_pulls += contributor.merged_pulls;
-                                    c.pending_pulls += contributor.pending_pulls;
-                                }
-                                _ => {
-                                    contributor_map.insert(
-                                        contributor.full_name.clone(),
-                                        ContributorCollection {
-                                            full_name: contributor.full_name.clone(),
-                                            profile_url: contributor.profile_url.clone(),
-                                            merged_pulls: contributor.merged_pulls,
-                                            pending_pulls: contributor.pending_pulls,
-                                        },
-                                    );
-                                }
-                            }
-                        }
-                    }
-                    Err(ScrapeError::InvalidRepo) => trace!("ignoring {}", repo.full_name),
-                    Err(e) => log::debug!("err {:?} -> {:?}", e, repo),
-                };
-
-                for pull in pulls.iter().filter(|pull| {
+                let repo_pulls_start = pull_request_collection.len();
+                for pull in pulls.into_iter().filter(|pull| {
                     pull.created_at.ge(&FIRST_OCTOBER) && pull.created_at.le(&LAST_OCTOBER)
                 }) {
-                    match scrape_pull_request(github_client, &username, repo, pull.number).await {
+                    match scrape_pull_request(
+                        github_client,
+                        username,
+                        repo,
+                        pull.number,
+                        &spam_policy,
+                    )
+                    .await
+                    {
                         Ok(pr) => pull_request_collection.push(pr),
                         Err(ScrapeError::InvalidRepo) => trace!("ignoring {}", repo.full_name),
-                        Err(e) => log::debug!("err {:?} -> {:?}", e, repo),
+                        Err(e) => {
+                            log::debug!("err {:?} -> {:?}", e, repo);
+                            unscraped_pulls.push(pull);
+                        }
                     };
                 }
+
+                if let Some(mut collection) = collection {
+                    collection.health = scrape_repository_health(
+                        &pull_request_collection[repo_pulls_start..],
+                        collection.issues.len(),
+                        Utc::now(),
+                    );
+                    repository_collection.push(collection);
+                }
             }
         }
     }
 
+    spam::flag_collection(&mut pull_request_collection, &spam_policy);
+
     let contributor_collection: Vec<ContributorCollection> =
-        contributor_map.into_values().collect();
+        scrape_contributor_collection(&pull_request_collection, &unscraped_pulls).await?;
+    let maintainer_collection: Vec<MaintainerCollection> =
+        scrape_maintainer_collection(&pull_request_collection).await?;
+
+    let snapshot = Snapshot {
+        repositories: repository_collection,
+        contributors: contributor_collection,
+        pulls: pull_request_collection,
+    };
 
-    let repository_json_collection: String =
-        serde_json::to_string(&repository_collection).map_err(ScrapeError::Serde)?;
-    let contributor_json_collection: String =
-        serde_json::to_string(&contributor_collection).map_err(ScrapeError::Serde)?;
-    let pull_request_json_collection: String =
-        serde_json::to_string(&pull_request_collection).map_err(ScrapeError::Serde)?;
+    Ok((snapshot, maintainer_collection))
+}
 
-    {
-        let g_ctx = ctx.lock().unwrap();
-        let mut _cfg = g_ctx.config.borrow_mut();
-        _cfg.scrape_last = Some(Local::now());
-        _cfg.cached_map.extend([
-            ("repo".into(), repository_json_collection),
-            ("contributors".into(), contributor_json_collection),
-            ("pull_request".into(), pull_request_json_collection),
-        ]);
+/// Scrape results for tests in any module, adjust the fields a test is about.
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use chrono::{DateTime, Utc};
+
+    use crate::events::Snapshot;
+    use crate::github::{Issue, User};
+
+    use super::{
+        ContributorCollection, PullRequestAuthorAssociation, PullRequestCollection,
+        PullRequestDiff, PullRequestMergeableState, PullRequestReview, PullRequestState,
+        RepositoryCollection, RepositoryHealth,
+    };
+
+    fn at(timestamp: &str) -> DateTime<Utc> {
+        timestamp.parse().unwrap()
     }
 
-    Ok(())
+    pub fn user(login: &str) -> User {
+        User {
+            login: login.to_owned(),
+            avatar_url: format!("https://avatars/{login}"),
+            html_url: format!("https://github.com/{login}"),
+        }
+    }
+
+    /// Opened on 2 October by `author` and merged a day later.
+    pub fn pull(repository: &str, number: i64, author: &str) -> PullRequestCollection {
+        PullRequestCollection {
+            repository: repository.to_owned(),
+            number,
+            html_url: format!("https://github.com/{repository}/pull/{number}"),
+            title: "docs: initialize deadme".to_owned(),
+            state: PullRequestState::Closed,
+            mergeable_state: PullRequestMergeableState::Unknown,
+            locked: false,
+            user: user(author),
+            created_at: at("2023-10-02T00:00:00Z"),
+            updated_at: at("2023-10-03T00:00:00Z"),
+            merged_at: at("2023-10-03T00:00:00Z"),
+            closed_at: at("2023-10-03T00:00:00Z"),
+            merged: true,
+            merged_by: None,
+            draft: false,
+            requested_reviewers: vec![],
+            author_association: PullRequestAuthorAssociation::Contributor,
+            comments: 0,
+            review_comments: 0,
+            diff: PullRequestDiff {
+                additions: 1,
+                deletions: 0,
+                changed_files: 1,
+            },
+            reviews: vec![],
+            spam_reasons: vec![],
+            excluded_from_scoring: false,
+        }
+    }
+
+    /// Like `pull`, but still waiting for a merge.
+    pub fn open_pull(repository: &str, number: i64, author: &str) -> PullRequestCollection {
+        PullRequestCollection {
+            state: PullRequestState::Open,
+            merged_at: DateTime::<Utc>::MIN_UTC,
+            closed_at: DateTime::<Utc>::MIN_UTC,
+            merged: false,
+            ..pull(repository, number, author)
+        }
+    }
+
+    pub fn review(login: &str, submitted_at: &str) -> PullRequestReview {
+        PullRequestReview {
+            user: user(login),
+            state: "APPROVED".to_owned(),
+            submitted_at: at(submitted_at),
+        }
+    }
+
+    /// `teknologi-umum/{name}` with `issues` open issues.
+    pub fn repository(name: &str, issues: usize) -> RepositoryCollection {
+        let issue = |number: usize| Issue {
+            node_id: format!("I_{number}"),
+            html_url: format!("https://github.com/teknologi-umum/{name}/issues/{number}"),
+            title: "Add tests".to_owned(),
+            comments: 0,
+            user: user("somebody"),
+            labels: vec![],
+            created_at: at("2023-10-02T00:00:00Z"),
+            updated_at: at("2023-10-02T00:00:00Z"),
+            closed_by: None,
+        };
+        RepositoryCollection {
+            full_name: format!("teknologi-umum/{name}"),
+            html_url: format!("https://github.com/teknologi-umum/{name}"),
+            description: None,
+            languages: vec!["PHP".to_owned()],
+            stars_count: 1,
+            forks_count: 0,
+            topics: vec!["hacktoberfest".to_owned()],
+            created_at: at("2023-10-02T00:00:00Z"),
+            updated_at: at("2023-10-02T00:00:00Z"),
+            issues: (1..=issues).map(issue).collect(),
+            health: RepositoryHealth::default(),
+        }
+    }
+
+    pub fn contributor(login: &str, merged_pulls: i64) -> ContributorCollection {
+        ContributorCollection {
+            full_name: login.to_owned(),
+            profile_url: format!("https://github.com/{login}"),
+            avatar_url: format!("https://avatars/{login}"),
+            merged_pulls,
+            pending_pulls: 0,
+        }
+    }
+
+    /// `somebody` with one merged pull request on `teknologi-umum/pehape`.
+    pub fn snapshot() -> Snapshot {
+        Snapshot {
+            repositories: vec![repository("pehape", 1)],
+            contributors: vec![contributor("somebody", 1)],
+            pulls: vec![pull("teknologi-umum/pehape", 1, "somebody")],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http;
+    use chrono::{DateTime, Utc};
+
+    use crate::github::GithubError;
+
+    use super::fixtures::{open_pull, pull, review, user};
+    use super::{
+        median, scrape_contributor_collection, scrape_maintainer_collection,
+        scrape_repository_health, RepositoryHealth, ScrapeError,
+    };
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(vec![]), None);
+        assert_eq!(median(vec![5]), Some(5));
+        assert_eq!(median(vec![9, 1, 5]), Some(5));
+        assert_eq!(median(vec![4, 1, 2, 3]), Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_scrape_contributor_collection() {
+        let mut closed = pull("teknologi-umum/pehape", 2, "contributor");
+        closed.merged_at = DateTime::<Utc>::MIN_UTC;
+        let mut flagged = pull("teknologi-umum/pehape", 3, "contributor");
+        flagged.excluded_from_scoring = true;
+        let pulls = vec![
+            pull("teknologi-umum/pehape", 1, "contributor"),
+            closed,
+            flagged,
+        ];
+        // its details failed to load
+        let unscraped = vec![serde_json::from_value(serde_json::json!({
+            "html_url": "https://github.com/teknologi-umum/pehape/pull/2",
+            "state": "open",
+            "title": "feat: add str_contains",
+            "number": 2,
+            "locked": false,
+            "user": {"login": "newcomer", "avatar_url": "", "html_url": ""},
+            "merged_at": null,
+            "closed_at": null,
+            "created_at": "2023-10-04T00:00:00Z",
+            "updated_at": "2023-10-04T00:00:00Z",
+        }))
+        .unwrap()];
+
+        let mut contributors = scrape_contributor_collection(&pulls, &unscraped)
+            .await
+            .unwrap();
+        contributors.sort_by(|a, b| a.full_name.cmp(&b.full_name));
+
+        assert_eq!(contributors.len(), 2);
+        assert_eq!(contributors[0].full_name, "contributor");
+        assert_eq!(contributors[0].merged_pulls, 1);
+        assert_eq!(contributors[0].pending_pulls, 1);
+        assert_eq!(contributors[1].full_name, "newcomer");
+        assert_eq!(contributors[1].merged_pulls, 0);
+        assert_eq!(contributors[1].pending_pulls, 1);
+    }
+
+    #[tokio::test]
+    async fn test_scrape_maintainer_collection() {
+        let mut merged = pull("teknologi-umum/pehape", 1, "contributor");
+        merged.merged_by = Some(user("maintainer"));
+        merged.reviews = vec![
+            review("contributor", "2023-10-02T00:10:00Z"),
+            review("maintainer", "2023-10-02T01:00:00Z"),
+            review("reviewer", "2023-10-02T02:00:00Z"),
+        ];
+        let mut reviewed = pull("teknologi-umum/pehape", 2, "contributor");
+        reviewed.reviews = vec![review("reviewer", "2023-10-02T00:30:00Z")];
+        let pulls = vec![merged, reviewed];
+        let mut maintainers = scrape_maintainer_collection(&pulls).await.unwrap();
+        maintainers.sort_by(|a, b| a.full_name.cmp(&b.full_name));
+
+        assert_eq!(maintainers.len(), 2);
+        assert_eq!(maintainers[0].full_name, "maintainer");
+        assert_eq!(maintainers[0].reviews_submitted, 1);
+        assert_eq!(maintainers[0].merged_pulls, 1);
+        assert_eq!(maintainers[0].median_first_review_secs, Some(3600));
+        assert_eq!(maintainers[1].full_name, "reviewer");
+        assert_eq!(maintainers[1].reviews_submitted, 2);
+        assert_eq!(maintainers[1].merged_pulls, 0);
+        assert_eq!(maintainers[1].median_first_review_secs, Some(1800));
+    }
+
+    #[test]
+    fn test_scrape_repository_health() {
+        let merged = pull("teknologi-umum/pehape", 1, "contributor");
+        let mut closed = pull("teknologi-umum/pehape", 2, "contributor");
+        closed.merged_at = DateTime::<Utc>::MIN_UTC;
+        closed.closed_at = "2023-10-02T00:10:00Z".parse().unwrap();
+        let mut open = open_pull("teknologi-umum/pehape", 3, "contributor");
+        open.reviews = vec![review("maintainer", "2023-10-02T00:30:00Z")];
+
+        let now = "2023-10-04T00:00:00Z".parse().unwrap();
+        let health = scrape_repository_health(&[merged, closed, open], 3, now);
+        assert_eq!(
+            health,
+            RepositoryHealth {
+                opened_pulls: 3,
+                merged_pulls: 1,
+                closed_unmerged_pulls: 1,
+                open_pulls: 1,
+                median_merge_secs: Some(86400),
+                median_first_response_secs: Some(1800),
+                open_backlog_median_age_secs: Some(172800),
+                open_backlog_oldest_age_secs: Some(172800),
+                open_hacktoberfest_issues: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn test_scrape_error_category() {
+        let forbidden = ScrapeError::Github(GithubError::StatusCode(http::StatusCode::FORBIDDEN));
+        assert_eq!(forbidden.category(), "github_status");
+        assert_eq!(ScrapeError::InvalidRepo.category(), "invalid_repository");
+    }
 }
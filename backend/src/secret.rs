@@ -0,0 +1,142 @@
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{convert::Infallible, fmt, fs, io, str::FromStr};
+
+/// A credential that never shows up in `Debug`, `Display` or logs, read it with
+/// `expose`. In the config file it is either the value itself or `{file: <path>}`,
+/// e.g. a Docker or Kubernetes secret mount, read when the config is loaded.
+#[derive(Clone, PartialEq, Eq, Default)]
+pub struct Secret {
+    value: String,
+    // written back as a reference, so rewriting the config never inlines the value
+    file: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum SecretRepr {
+    Value(String),
+    File { file: String },
+}
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self {
+            value: value.into(),
+            file: None,
+        }
+    }
+
+    /// Reads a secret file, without the trailing newline most editors and
+    /// `echo` leave behind.
+    pub fn from_file(path: &str) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        Ok(Self {
+            value: contents.trim_end_matches(['\r', '\n']).to_owned(),
+            file: Some(path.to_owned()),
+        })
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.value
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.value.is_empty()
+    }
+
+    /// Whether the value itself was given rather than a file holding it.
+    pub fn is_inline(&self) -> bool {
+        self.file.is_none()
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "Secret(<redacted>, file: {file:?})"),
+            None => write!(f, "Secret(<redacted>)"),
+        }
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<redacted>")
+    }
+}
+
+impl FromStr for Secret {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::new(s))
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match &self.file {
+            Some(file) => SecretRepr::File { file: file.clone() },
+            None => SecretRepr::Value(self.value.clone()),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match SecretRepr::deserialize(deserializer)? {
+            SecretRepr::Value(value) => Ok(Self::new(value)),
+            SecretRepr::File { file } => Self::from_file(&file)
+                .map_err(|e| serde::de::Error::custom(format!("secret file {file}: {e}"))),
+        }
+    }
+}
+
+impl JsonSchema for Secret {
+    fn schema_name() -> String {
+        "Secret".to_owned()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        SecretRepr::json_schema(gen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::Secret;
+
+    #[test]
+    fn test_redacted() {
+        let secret = Secret::new("hunter2");
+        assert_eq!(format!("{secret:?}"), "Secret(<redacted>)");
+        assert_eq!(secret.to_string(), "<redacted>");
+        assert_eq!(secret.expose(), "hunter2");
+    }
+
+    #[test]
+    fn test_file() -> anyhow::Result<()> {
+        let path = env::temp_dir()
+            .join(format!("hacktoberfest-{}-secret", process::id()))
+            .to_string_lossy()
+            .into_owned();
+        fs::write(&path, "hunter2\n")?;
+
+        let yaml = format!("file: {path}");
+        let secret: Secret = serde_yaml::from_str(&yaml)?;
+        assert_eq!(secret.expose(), "hunter2");
+        // the reference is written back, not the value
+        assert_eq!(serde_yaml::to_string(&secret)?.trim_end(), yaml);
+
+        let inline: Secret = serde_yaml::from_str("hunter2")?;
+        assert_eq!(inline.expose(), "hunter2");
+
+        fs::remove_file(&path)?;
+        assert!(serde_yaml::from_str::<Secret>(&yaml).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,499 @@
+use crate::config::ServerSettings;
+use crate::secret::Secret;
+use anyhow::{anyhow, bail, Result};
+use clap::{clap_app, ArgMatches};
+use std::fmt::{self, Display};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Where the effective value of a setting came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    File(String),
+    Env(String),
+    Cli(&'static str),
+    // mounted by Docker or Kubernetes
+    SecretFile(&'static str),
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Default => write!(f, "default"),
+            Self::File(path) => write!(f, "config file {path}"),
+            Self::Env(name) => write!(f, "env {name}"),
+            Self::Cli(flag) => write!(f, "flag --{flag}"),
+            Self::SecretFile(path) => write!(f, "secret file {path}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Setting<T> {
+    pub value: T,
+    pub source: Source,
+}
+
+/// How a setting is named in each layer. Env vars are `HACKTOBERFEST_<KEY>`, with
+/// the names older versions read still accepted.
+struct Spec {
+    key: &'static str,
+    flag: &'static str,
+    legacy_env: &'static [&'static str],
+}
+
+// used when no layer sets a value, and by `RunContext::default`
+pub const DEFAULT_LISTEN_ADDRESS: &str = "127.0.0.1:8080";
+pub const DEFAULT_NUM_WORKERS: usize = 1;
+pub const DEFAULT_SCRAPE_INTERVAL: u64 = 3600;
+pub const DEFAULT_CONFIG_PATH: &str = "/tmp/data.yml";
+pub const DEFAULT_STATE_DIR: &str = "/tmp/hacktoberfest";
+pub const DEFAULT_BACKUP_COUNT: usize = 3;
+pub const DEFAULT_SCRAPE_PER_PAGE: u8 = 100;
+
+const LISTEN_ADDRESS: Spec = Spec {
+    key: "listen_address",
+    flag: "addr",
+    legacy_env: &["LISTEN_ADDR"],
+};
+const NUM_WORKERS: Spec = Spec {
+    key: "num_workers",
+    flag: "wrk",
+    legacy_env: &["NUM_WORKERS"],
+};
+const SCRAPE_INTERVAL: Spec = Spec {
+    key: "scrape_interval",
+    flag: "scrape_interval",
+    legacy_env: &["scrape_interval", "SCRAPE_INTERVAL"],
+};
+const GITHUB_TOKEN: Spec = Spec {
+    key: "github_token",
+    flag: "github_token",
+    legacy_env: &["GITHUB_TOKEN"],
+};
+/// Read when no other layer sets the token, Docker mounts `secrets:` here.
+const GITHUB_TOKEN_SECRET_FILE: &str = "/run/secrets/github_token";
+
+const CONFIG_PATH: Spec = Spec {
+    key: "config_path",
+    flag: "config_path",
+    legacy_env: &["CONFIG_PATH"],
+};
+const STATE_DIR: Spec = Spec {
+    key: "state_dir",
+    flag: "state_dir",
+    legacy_env: &["STATE_DIR"],
+};
+const BACKUP_COUNT: Spec = Spec {
+    key: "backup_count",
+    flag: "backup_count",
+    legacy_env: &["BACKUP_COUNT"],
+};
+const SCRAPE_PER_PAGE: Spec = Spec {
+    key: "scrape_per_page",
+    flag: "scrape_per_page",
+    legacy_env: &["scrape_per_page"],
+};
+
+pub fn app() -> clap::Command<'static> {
+    clap_app!(hacktoberfestd =>
+        (version: "")
+        (about: "Hacktoberfest serverd")
+        (@arg addr: --addr +takes_value "Listen address for HTTP server")
+        (@arg wrk: --wrk +takes_value "Number of HTTP server workers")
+        (@arg scrape_interval: --("scrape_interval") +takes_value "Scrap interval in second")
+        (@arg github_token: --("github_token") +takes_value "Github API Token, visible to other users in the process list")
+        (@arg github_token_file: --("github_token_file") +takes_value "File holding the Github API Token")
+        (@arg config_path: --("config_path") +takes_value "Config path")
+        (@arg scrape_per_page: --("scrape_per_page") +takes_value "Github scrap per_page limit")
+        (@arg state_dir: --("state_dir") +takes_value "Directory for scraped data and other runtime state")
+        (@arg backup_count: --("backup_count") +takes_value "Number of store backups to keep")
+    )
+    .arg(
+        clap::Arg::new("print_config")
+            .long("print-config")
+            .help("Print the effective settings and where each came from, then exit"),
+    )
+    .subcommand(
+        clap::Command::new("validate-config")
+            .about("Check the config file and exit, non-zero if it has errors"),
+    )
+    .subcommand(
+        clap::Command::new("migrate-config")
+            .about("Upgrade the config file to the current version, comments are not kept")
+            .arg(
+                clap::Arg::new("dry_run")
+                    .long("dry-run")
+                    .help("Print the migrated config instead of writing it"),
+            ),
+    )
+    .subcommand(
+        clap::Command::new("config-schema").about("Print the JSON Schema of the config file"),
+    )
+    .subcommand(
+        clap::Command::new("openapi").about("Print the OpenAPI document of the public API"),
+    )
+}
+
+/// The layers above the defaults, lowest priority first: config file, env, CLI.
+struct Layers<'a> {
+    file_path: &'a str,
+    env: &'a dyn Fn(&str) -> Option<String>,
+    matches: &'a ArgMatches,
+}
+
+impl Layers<'_> {
+    fn resolve<T>(&self, spec: &Spec, default: T, file: Option<T>) -> Result<Setting<T>>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let parse = |raw: &str, source: Source| -> Result<Setting<T>> {
+            let value = raw
+                .parse()
+                .map_err(|e| anyhow!("{}: invalid value {raw:?} from {source}: {e}", spec.key))?;
+            Ok(Setting { value, source })
+        };
+
+        if let Some(raw) = self.matches.get_one::<String>(spec.flag) {
+            return parse(raw, Source::Cli(spec.flag));
+        }
+        let env_name = format!("HACKTOBERFEST_{}", spec.key.to_uppercase());
+        if let Some(raw) = (self.env)(&env_name) {
+            return parse(&raw, Source::Env(env_name));
+        }
+        for legacy in spec.legacy_env.iter() {
+            if let Some(raw) = (self.env)(legacy) {
+                log::warn!("env {legacy} is deprecated, use {env_name}");
+                return parse(&raw, Source::Env(legacy.to_string()));
+            }
+        }
+        Ok(match file {
+            Some(value) => Setting {
+                value,
+                source: Source::File(self.file_path.to_owned()),
+            },
+            None => Setting {
+                value: default,
+                source: Source::Default,
+            },
+        })
+    }
+}
+
+impl Layers<'_> {
+    /// Like `resolve`, but every layer may also point to a file holding the secret:
+    /// `--<file_flag>`, `HACKTOBERFEST_<KEY>_FILE`, `<LEGACY>_FILE`, `{file: <path>}`
+    /// in the config file, and lastly `secret_file`.
+    fn resolve_secret(
+        &self,
+        spec: &Spec,
+        file_flag: &'static str,
+        file: Option<Secret>,
+        secret_file: &'static str,
+    ) -> Result<Setting<Secret>> {
+        let read = |path: &str, source: Source| -> Result<Setting<Secret>> {
+            let value = Secret::from_file(path)
+                .map_err(|e| anyhow!("{}: can't read {path} from {source}: {e}", spec.key))?;
+            Ok(Setting { value, source })
+        };
+
+        if let Some(path) = self.matches.get_one::<String>(file_flag) {
+            return read(path, Source::Cli(file_flag));
+        }
+        if let Some(value) = self.matches.get_one::<String>(spec.flag) {
+            log::warn!(
+                "--{} is visible in the process list, prefer --{file_flag}",
+                spec.flag
+            );
+            return Ok(Setting {
+                value: Secret::new(value.as_str()),
+                source: Source::Cli(spec.flag),
+            });
+        }
+
+        let env_name = format!("HACKTOBERFEST_{}", spec.key.to_uppercase());
+        let legacy_env = spec.legacy_env.iter().map(|name| name.to_string());
+        for name in std::iter::once(env_name.clone()).chain(legacy_env) {
+            let file_name = format!("{name}_FILE");
+            if let Some(path) = (self.env)(&file_name) {
+                return read(&path, Source::Env(file_name));
+            }
+            if let Some(value) = (self.env)(&name) {
+                if name != env_name {
+                    log::warn!("env {name} is deprecated, use {env_name} or {env_name}_FILE");
+                }
+                return Ok(Setting {
+                    value: Secret::new(value),
+                    source: Source::Env(name),
+                });
+            }
+        }
+
+        if let Some(value) = file {
+            return Ok(Setting {
+                value,
+                source: Source::File(self.file_path.to_owned()),
+            });
+        }
+        if Path::new(secret_file).exists() {
+            return read(secret_file, Source::SecretFile(secret_file));
+        }
+        Ok(Setting {
+            value: Secret::default(),
+            source: Source::Default,
+        })
+    }
+}
+
+/// Every `RunContext` setting with its effective value: defaults, overridden by the
+/// config file's `server` section, then `HACKTOBERFEST_*` env vars, then CLI flags.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub listen_address: Setting<String>,
+    pub num_workers: Setting<usize>,
+    pub scrape_interval: Setting<u64>,
+    pub github_token: Setting<Secret>,
+    pub config_path: Setting<String>,
+    pub state_dir: Setting<String>,
+    pub backup_count: Setting<usize>,
+    pub scrape_per_page: Setting<u8>,
+}
+
+impl Settings {
+    /// The config file can't say where it is, so its path comes from the other layers.
+    pub fn config_path(
+        matches: &ArgMatches,
+        env: &dyn Fn(&str) -> Option<String>,
+    ) -> Result<Setting<String>> {
+        let layers = Layers {
+            file_path: "",
+            env,
+            matches,
+        };
+        layers.resolve(&CONFIG_PATH, DEFAULT_CONFIG_PATH.to_owned(), None)
+    }
+
+    pub fn load(
+        matches: &ArgMatches,
+        env: &dyn Fn(&str) -> Option<String>,
+        config_path: Setting<String>,
+        file: &ServerSettings,
+    ) -> Result<Self> {
+        let layers = Layers {
+            file_path: &config_path.value,
+            env,
+            matches,
+        };
+
+        let settings = Self {
+            listen_address: layers.resolve(
+                &LISTEN_ADDRESS,
+                DEFAULT_LISTEN_ADDRESS.to_owned(),
+                file.listen_address.clone(),
+            )?,
+            num_workers: layers.resolve(&NUM_WORKERS, DEFAULT_NUM_WORKERS, file.num_workers)?,
+            scrape_interval: layers.resolve(
+                &SCRAPE_INTERVAL,
+                DEFAULT_SCRAPE_INTERVAL,
+                file.scrape_interval,
+            )?,
+            github_token: layers.resolve_secret(
+                &GITHUB_TOKEN,
+                "github_token_file",
+                file.github_token.clone(),
+                GITHUB_TOKEN_SECRET_FILE,
+            )?,
+            state_dir: layers.resolve(
+                &STATE_DIR,
+                DEFAULT_STATE_DIR.to_owned(),
+                file.state_dir.clone(),
+            )?,
+            backup_count: layers.resolve(&BACKUP_COUNT, DEFAULT_BACKUP_COUNT, file.backup_count)?,
+            scrape_per_page: layers.resolve(
+                &SCRAPE_PER_PAGE,
+                DEFAULT_SCRAPE_PER_PAGE,
+                file.scrape_per_page,
+            )?,
+            config_path,
+        };
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.num_workers.value == 0 {
+            bail!(
+                "num_workers: must be at least 1, got 0 from {}",
+                self.num_workers.source
+            );
+        }
+        if self.scrape_interval.value == 0 {
+            bail!(
+                "scrape_interval: must be at least 1, got 0 from {}",
+                self.scrape_interval.source
+            );
+        }
+        if !(1..=100).contains(&self.scrape_per_page.value) {
+            bail!(
+                "scrape_per_page: must be between 1 and 100, got {} from {}",
+                self.scrape_per_page.value,
+                self.scrape_per_page.source
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Display for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let token = if self.github_token.value.is_empty() {
+            "\"\"".to_owned()
+        } else {
+            self.github_token.value.to_string()
+        };
+        let rows = [
+            (
+                "listen_address",
+                format!("{:?}", self.listen_address.value),
+                &self.listen_address.source,
+            ),
+            (
+                "num_workers",
+                self.num_workers.value.to_string(),
+                &self.num_workers.source,
+            ),
+            (
+                "scrape_interval",
+                self.scrape_interval.value.to_string(),
+                &self.scrape_interval.source,
+            ),
+            ("github_token", token, &self.github_token.source),
+            (
+                "config_path",
+                format!("{:?}", self.config_path.value),
+                &self.config_path.source,
+            ),
+            (
+                "state_dir",
+                format!("{:?}", self.state_dir.value),
+                &self.state_dir.source,
+            ),
+            (
+                "backup_count",
+                self.backup_count.value.to_string(),
+                &self.backup_count.source,
+            ),
+            (
+                "scrape_per_page",
+                self.scrape_per_page.value.to_string(),
+                &self.scrape_per_page.source,
+            ),
+        ];
+        for (key, value, source) in rows.iter() {
+            writeln!(f, "{key:<16} = {value:<24} # {source}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::{env, fs, process};
+
+    use crate::config::ServerSettings;
+    use crate::secret::Secret;
+
+    use super::{app, Settings, Source};
+
+    #[test]
+    fn test_layering() -> anyhow::Result<()> {
+        let vars = HashMap::from([
+            ("HACKTOBERFEST_NUM_WORKERS", "2"),
+            ("HACKTOBERFEST_SCRAPE_INTERVAL", "60"),
+            ("SCRAPE_INTERVAL", "7200"),
+            ("scrape_per_page", "50"),
+            ("HACKTOBERFEST_CONFIG_PATH", "/etc/hacktoberfest.yml"),
+        ]);
+        let env = |name: &str| vars.get(name).map(|value| value.to_string());
+        let matches = app().try_get_matches_from(["hacktoberfest", "--wrk", "4"])?;
+        let file = ServerSettings {
+            listen_address: Some("0.0.0.0:9090".to_owned()),
+            num_workers: Some(8),
+            ..Default::default()
+        };
+
+        let config_path = Settings::config_path(&matches, &env)?;
+        let settings = Settings::load(&matches, &env, config_path, &file)?;
+
+        assert_eq!(settings.num_workers.value, 4);
+        assert_eq!(settings.num_workers.source, Source::Cli("wrk"));
+        assert_eq!(settings.scrape_interval.value, 60);
+        assert_eq!(settings.scrape_per_page.value, 50);
+        assert_eq!(
+            settings.scrape_per_page.source,
+            Source::Env("scrape_per_page".to_owned())
+        );
+        assert_eq!(settings.listen_address.value, "0.0.0.0:9090");
+        assert_eq!(
+            settings.listen_address.source,
+            Source::File("/etc/hacktoberfest.yml".to_owned())
+        );
+        assert_eq!(settings.backup_count.source, Source::Default);
+        Ok(())
+    }
+
+    #[test]
+    fn test_github_token() -> anyhow::Result<()> {
+        let path = env::temp_dir()
+            .join(format!("hacktoberfest-{}-github-token", process::id()))
+            .to_string_lossy()
+            .into_owned();
+        fs::write(&path, "ghp_fromfile\n")?;
+        let file = ServerSettings {
+            github_token: Some(Secret::new("ghp_fromconfig")),
+            ..Default::default()
+        };
+        let matches = app().try_get_matches_from(["hacktoberfest"])?;
+
+        let vars = HashMap::from([
+            ("GITHUB_TOKEN", "ghp_legacy".to_owned()),
+            ("HACKTOBERFEST_GITHUB_TOKEN_FILE", path.clone()),
+        ]);
+        let env = |name: &str| vars.get(name).cloned();
+        let config_path = Settings::config_path(&matches, &env)?;
+        let settings = Settings::load(&matches, &env, config_path.clone(), &file)?;
+        assert_eq!(settings.github_token.value.expose(), "ghp_fromfile");
+        assert!(!settings.to_string().contains("ghp_"));
+
+        let no_env = |_: &str| None;
+        let settings = Settings::load(&matches, &no_env, config_path.clone(), &file)?;
+        assert_eq!(settings.github_token.value.expose(), "ghp_fromconfig");
+
+        let missing = |name: &str| {
+            (name == "GITHUB_TOKEN_FILE").then(|| "/nonexistent/github_token".to_owned())
+        };
+        assert!(Settings::load(&matches, &missing, config_path, &file).is_err());
+
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid() -> anyhow::Result<()> {
+        let env = |name: &str| (name == "HACKTOBERFEST_NUM_WORKERS").then(|| "many".to_owned());
+        let matches = app().try_get_matches_from(["hacktoberfest"])?;
+        let config_path = Settings::config_path(&matches, &env)?;
+        let err = Settings::load(&matches, &env, config_path.clone(), &Default::default())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("HACKTOBERFEST_NUM_WORKERS"));
+
+        let no_env = |_: &str| None;
+        let matches = app().try_get_matches_from(["hacktoberfest", "--scrape_per_page", "0"])?;
+        assert!(Settings::load(&matches, &no_env, config_path, &Default::default()).is_err());
+        Ok(())
+    }
+}
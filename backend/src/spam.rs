@@ -0,0 +1,231 @@
+use crate::config::SpamPolicy;
+use crate::github::{PullRequestFile, User};
+use crate::scraper::{PullRequestCollection, PullRequestState};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
+pub enum SpamReason {
+    /// Every changed line only differs in whitespace.
+    WhitespaceOnly,
+    /// Only README files were changed.
+    ReadmeOnly,
+    /// At most `SpamPolicy::tiny_diff_max_lines` changed lines, if that is set.
+    TinyDiff,
+    /// The author opened pull requests with the same title on many repositories.
+    DuplicateAcrossRepos,
+    /// Closed without merge by someone else shortly after being opened.
+    ClosedQuickly,
+}
+
+pub fn is_whitespace_only(files: &[PullRequestFile]) -> bool {
+    let strip = |lines: Vec<&str>| -> String {
+        lines
+            .concat()
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect()
+    };
+
+    !files.is_empty()
+        && files.iter().all(|file| match &file.patch {
+            Some(patch) => {
+                let removed = patch
+                    .lines()
+                    .filter_map(|line| line.strip_prefix('-'))
+                    .collect();
+                let added = patch
+                    .lines()
+                    .filter_map(|line| line.strip_prefix('+'))
+                    .collect();
+                strip(removed) == strip(added)
+            }
+            // binary or oversized diff, can't tell
+            None => false,
+        })
+}
+
+pub fn is_readme_only(files: &[PullRequestFile]) -> bool {
+    !files.is_empty()
+        && files.iter().all(|file| {
+            let basename = file.filename.rsplit('/').next().unwrap_or_default();
+            basename.to_uppercase().starts_with("README")
+        })
+}
+
+/// Whether the pull request was closed without merge soon enough after being opened
+/// to be worth asking who closed it.
+pub fn is_quick_close_candidate(pull: &PullRequestCollection, policy: &SpamPolicy) -> bool {
+    pull.state == PullRequestState::Closed
+        && !pull.is_merged()
+        && pull
+            .closed_at
+            .signed_duration_since(pull.created_at)
+            .num_minutes()
+            < policy.quick_close_minutes
+}
+
+/// Flags a single pull request from its changed files and, for quickly closed ones,
+/// the user who closed it.
+pub fn detect_pull_request(
+    pull: &PullRequestCollection,
+    files: &[PullRequestFile],
+    closed_by: Option<&User>,
+    policy: &SpamPolicy,
+) -> Vec<SpamReason> {
+    let mut reasons = Vec::new();
+    if is_whitespace_only(files) {
+        reasons.push(SpamReason::WhitespaceOnly);
+    }
+    if is_readme_only(files) {
+        reasons.push(SpamReason::ReadmeOnly);
+    }
+    if policy.tiny_diff_max_lines > 0
+        && pull.diff.additions + pull.diff.deletions <= policy.tiny_diff_max_lines
+    {
+        reasons.push(SpamReason::TinyDiff);
+    }
+    if let Some(closed_by) = closed_by {
+        if closed_by.login != pull.user.login && is_quick_close_candidate(pull, policy) {
+            reasons.push(SpamReason::ClosedQuickly);
+        }
+    }
+    reasons
+}
+
+fn normalize_title(title: &str) -> String {
+    title
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// Flags pull requests sharing an author and title across repositories, then marks
+/// every flagged pull request as excluded from scoring if the policy says so.
+pub fn flag_collection(pulls: &mut [PullRequestCollection], policy: &SpamPolicy) {
+    if !policy.enabled {
+        return;
+    }
+
+    let key = |pull: &PullRequestCollection| {
+        (pull.user.login.to_lowercase(), normalize_title(&pull.title))
+    };
+    let mut repos_by_key = HashMap::<(String, String), HashSet<String>>::new();
+    for pull in pulls.iter() {
+        repos_by_key
+            .entry(key(pull))
+            .or_default()
+            .insert(pull.repository.clone());
+    }
+
+    for pull in pulls.iter_mut() {
+        let duplicated = repos_by_key
+            .get(&key(pull))
+            .is_some_and(|repos| repos.len() >= policy.duplicate_min_repos);
+        if duplicated
+            && !pull
+                .spam_reasons
+                .contains(&SpamReason::DuplicateAcrossRepos)
+        {
+            pull.spam_reasons.push(SpamReason::DuplicateAcrossRepos);
+        }
+        pull.excluded_from_scoring = policy.exclude_flagged && !pull.spam_reasons.is_empty();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::SpamPolicy;
+    use crate::github::PullRequestFile;
+    use crate::scraper::{fixtures, PullRequestCollection};
+
+    use super::{
+        detect_pull_request, flag_collection, is_readme_only, is_whitespace_only, SpamReason,
+    };
+
+    fn file(filename: &str, patch: Option<&str>) -> PullRequestFile {
+        PullRequestFile {
+            filename: filename.to_owned(),
+            status: "modified".to_owned(),
+            additions: 1,
+            deletions: 1,
+            changes: 2,
+            patch: patch.map(str::to_owned),
+        }
+    }
+
+    fn pull(repository: &str, author: &str, title: &str) -> PullRequestCollection {
+        PullRequestCollection {
+            title: title.to_owned(),
+            ..fixtures::open_pull(repository, 1, author)
+        }
+    }
+
+    #[test]
+    fn test_is_whitespace_only() {
+        let reformatted = file(
+            "main.rs",
+            Some("@@ -1 +1 @@\n-fn  main() {}\n+fn main() {}"),
+        );
+        let changed = file(
+            "main.rs",
+            Some("@@ -1 +1 @@\n-fn main() {}\n+fn main() { }\n+x"),
+        );
+        let binary = file("logo.png", None);
+
+        assert!(is_whitespace_only(&[reformatted]));
+        assert!(!is_whitespace_only(&[changed]));
+        assert!(!is_whitespace_only(&[binary]));
+        assert!(!is_whitespace_only(&[]));
+    }
+
+    #[test]
+    fn test_is_readme_only() {
+        assert!(is_readme_only(&[
+            file("README.md", None),
+            file("docs/readme.id.md", None)
+        ]));
+        assert!(!is_readme_only(&[
+            file("README.md", None),
+            file("src/main.rs", None)
+        ]));
+    }
+
+    #[test]
+    fn test_tiny_diff() {
+        let mut one_line = pull("a/one", "contributor", "Fix typo");
+        one_line.diff.additions = 1;
+        let files = [file("src/main.rs", Some("@@ -1 +1 @@\n-teh\n+the"))];
+
+        let reasons = detect_pull_request(&one_line, &files, None, &SpamPolicy::default());
+        assert!(reasons.is_empty());
+
+        let policy = SpamPolicy {
+            tiny_diff_max_lines: 1,
+            ..Default::default()
+        };
+        let reasons = detect_pull_request(&one_line, &files, None, &policy);
+        assert_eq!(reasons, vec![SpamReason::TinyDiff]);
+    }
+
+    #[test]
+    fn test_flag_collection() {
+        let mut pulls = vec![
+            pull("a/one", "spammer", "Update README.md"),
+            pull("b/two", "spammer", "update readme.md!"),
+            pull("c/three", "Spammer", "Update   README.md"),
+            pull("a/one", "contributor", "Update README.md"),
+        ];
+        flag_collection(&mut pulls, &SpamPolicy::default());
+
+        for pull in pulls.iter().take(3) {
+            assert_eq!(pull.spam_reasons, vec![SpamReason::DuplicateAcrossRepos]);
+            assert!(pull.excluded_from_scoring);
+        }
+        assert!(pulls[3].spam_reasons.is_empty());
+        assert!(!pulls[3].excluded_from_scoring);
+    }
+}
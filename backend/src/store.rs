@@ -0,0 +1,1014 @@
+use crate::config::{LegacyState, ScrapeTarget};
+use crate::events::{Event, EventKind, Snapshot, MAX_EVENTS};
+use crate::lock::LockRecover;
+use crate::persist;
+use crate::scraper::{
+    ContributorCollection, MaintainerCollection, PullRequestCollection, RepositoryCollection,
+};
+use actix_web::{error::BlockingError, web};
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::broadcast;
+
+/// Events buffered for each subscriber, see `Store::subscribe`.
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+const SCHEMA: &str = r#"
+PRAGMA foreign_keys = ON;
+
+CREATE TABLE IF NOT EXISTS scrape_runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    started_at TEXT NOT NULL,
+    finished_at TEXT NOT NULL,
+    succeeded INTEGER NOT NULL,
+    error TEXT
+);
+
+CREATE TABLE IF NOT EXISTS repositories (
+    full_name TEXT PRIMARY KEY,
+    position INTEGER NOT NULL,
+    data TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS issues (
+    html_url TEXT PRIMARY KEY,
+    repository TEXT NOT NULL REFERENCES repositories (full_name) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    data TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS issues_repository ON issues (repository);
+-- GitHub names are case-insensitive
+CREATE INDEX IF NOT EXISTS repositories_full_name_nocase ON repositories (full_name COLLATE NOCASE);
+
+CREATE TABLE IF NOT EXISTS pull_requests (
+    html_url TEXT PRIMARY KEY,
+    repository TEXT NOT NULL,
+    number INTEGER NOT NULL,
+    author TEXT NOT NULL COLLATE NOCASE,
+    flagged INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    data TEXT NOT NULL
+);
+CREATE UNIQUE INDEX IF NOT EXISTS pull_requests_number ON pull_requests (repository, number);
+CREATE INDEX IF NOT EXISTS pull_requests_author ON pull_requests (author);
+CREATE INDEX IF NOT EXISTS pull_requests_number_nocase
+    ON pull_requests (repository COLLATE NOCASE, number);
+
+CREATE TABLE IF NOT EXISTS contributors (
+    login TEXT PRIMARY KEY COLLATE NOCASE,
+    profile_url TEXT NOT NULL,
+    avatar_url TEXT NOT NULL,
+    merged_pulls INTEGER NOT NULL,
+    pending_pulls INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS maintainers (
+    login TEXT PRIMARY KEY COLLATE NOCASE,
+    profile_url TEXT NOT NULL,
+    avatar_url TEXT NOT NULL,
+    reviews_submitted INTEGER NOT NULL,
+    merged_pulls INTEGER NOT NULL,
+    median_first_review_secs INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    data TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS events_timestamp ON events (timestamp);
+
+-- single row, hash of the data saved by the last scrape
+CREATE TABLE IF NOT EXISTS snapshot_version (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    etag TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    actor TEXT NOT NULL,
+    data TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS target_overrides (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    key TEXT NOT NULL UNIQUE,
+    data TEXT
+);
+
+-- every issue and pull request seen by a scrape, see `EventKind::subject`
+CREATE TABLE IF NOT EXISTS announced (
+    kind TEXT NOT NULL,
+    html_url TEXT NOT NULL,
+    PRIMARY KEY (kind, html_url)
+);
+"#;
+
+#[derive(Debug)]
+pub enum StoreError {
+    Sqlite(rusqlite::Error),
+    Serde(serde_json::Error),
+    Io(io::Error),
+    // the blocking thread pool is shutting down
+    Blocking(BlockingError),
+}
+
+impl std::error::Error for StoreError {}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Sqlite(err) => err.fmt(f),
+            Self::Serde(err) => err.fmt(f),
+            Self::Io(err) => err.fmt(f),
+            Self::Blocking(err) => err.fmt(f),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(err: rusqlite::Error) -> Self {
+        Self::Sqlite(err)
+    }
+}
+
+impl From<serde_json::Error> for StoreError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Serde(err)
+    }
+}
+
+impl From<io::Error> for StoreError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ScrapeRun {
+    pub id: i64,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub succeeded: bool,
+    pub error: Option<String>,
+}
+
+/// What was done to a scrape target.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    Add,
+    Update,
+    Ignore,
+    Unignore,
+    Delete,
+}
+
+/// A change made through the admin API to the target with `key`, see
+/// `ScrapeTarget::key`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AuditChange {
+    pub action: AuditAction,
+    pub key: String,
+    pub before: Option<ScrapeTarget>,
+    pub after: Option<ScrapeTarget>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub timestamp: DateTime<Utc>,
+    // name of the admin key used
+    pub actor: String,
+    #[serde(flatten)]
+    pub change: AuditChange,
+}
+
+/// Fixed-width RFC 3339, so timestamps stored as text sort chronologically.
+fn timestamp(dt: &DateTime<Utc>) -> String {
+    dt.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+fn parse_timestamp(idx: usize, value: String) -> rusqlite::Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(&value)
+        .map(|dt| dt.with_timezone(&Utc))
+        .map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, Box::new(e))
+        })
+}
+
+fn legacy_collection<T: DeserializeOwned>(
+    cached_map: &HashMap<String, String>,
+    key: &str,
+) -> Result<Vec<T>, StoreError> {
+    match cached_map.get(key) {
+        Some(cached) => Ok(serde_json::from_str(cached)?),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Scraped data, persisted in SQLite. Every scrape replaces the previous one in a
+/// single transaction, so readers never see a half-written snapshot.
+pub struct Store {
+    conn: Mutex<Connection>,
+    // every recorded event, once committed, see `subscribe`
+    events: broadcast::Sender<Event>,
+}
+
+impl Store {
+    pub fn open(path: &String) -> Result<Self, StoreError> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self, StoreError> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self, StoreError> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+        })
+    }
+
+    /// Runs `query` on the blocking thread pool. Every call waits for the connection
+    /// and the disk, from a handler it would hold up the other requests on its worker.
+    pub async fn blocking<T, F>(self: &Arc<Self>, query: F) -> Result<T, StoreError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Self) -> Result<T, StoreError> + Send + 'static,
+    {
+        let store = self.clone();
+        web::block(move || query(&store))
+            .await
+            .map_err(StoreError::Blocking)?
+    }
+
+    /// Events recorded from now on. A receiver that falls behind by more than
+    /// `EVENT_CHANNEL_CAPACITY` lags, it can catch up with `events_after`.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    // called before the connection is unlocked, so subscribers see ids in order
+    fn publish(&self, recorded: &[Event]) {
+        for event in recorded.iter() {
+            // fails when nobody is subscribed
+            let _ = self.events.send(event.clone());
+        }
+    }
+
+    fn insert_events(
+        tx: &Transaction,
+        changes: Vec<EventKind>,
+        timestamp_: DateTime<Utc>,
+    ) -> Result<Vec<Event>, StoreError> {
+        let mut recorded = Vec::with_capacity(changes.len());
+        for kind in changes {
+            tx.execute(
+                "INSERT INTO events (timestamp, data) VALUES (?1, ?2)",
+                params![timestamp(&timestamp_), serde_json::to_string(&kind)?],
+            )?;
+            recorded.push(Event {
+                id: tx.last_insert_rowid() as u64,
+                timestamp: timestamp_,
+                kind,
+            });
+        }
+        tx.execute(
+            "DELETE FROM events WHERE id <= (SELECT MAX(id) FROM events) - ?1",
+            params![MAX_EVENTS as i64],
+        )?;
+        Ok(recorded)
+    }
+
+    /// Replaces the stored snapshot with `snapshot` and appends `changes` to the event
+    /// log, returning the recorded events. Changes about an issue or pull request seen
+    /// by an earlier scrape are dropped, it only dropped out of the scrapes in between,
+    /// e.g. when GitHub failed or its target was ignored.
+    pub fn save_scrape(
+        &self,
+        started_at: DateTime<Utc>,
+        snapshot: &Snapshot,
+        maintainers: &[MaintainerCollection],
+        changes: Vec<EventKind>,
+    ) -> Result<Vec<Event>, StoreError> {
+        let mut conn = self.conn.lock_recover();
+        let tx = conn.transaction()?;
+
+        tx.execute_batch(
+            "DELETE FROM issues; DELETE FROM repositories; DELETE FROM pull_requests;
+             DELETE FROM contributors; DELETE FROM maintainers;",
+        )?;
+
+        for (position, repo) in snapshot.repositories.iter().enumerate() {
+            let mut data = serde_json::to_value(repo)?;
+            if let Some(fields) = data.as_object_mut() {
+                // issues have their own table
+                fields.remove("issues");
+            }
+            tx.execute(
+                "INSERT INTO repositories (full_name, position, data) VALUES (?1, ?2, ?3)",
+                params![repo.full_name, position as i64, data.to_string()],
+            )?;
+            for (position, issue) in repo.issues.iter().enumerate() {
+                tx.execute(
+                    "INSERT OR REPLACE INTO issues (html_url, repository, position, data)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![
+                        issue.html_url,
+                        repo.full_name,
+                        position as i64,
+                        serde_json::to_string(issue)?
+                    ],
+                )?;
+            }
+        }
+
+        for (position, pull) in snapshot.pulls.iter().enumerate() {
+            tx.execute(
+                "INSERT OR REPLACE INTO pull_requests
+                 (html_url, repository, number, author, flagged, created_at, position, data)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    pull.html_url,
+                    pull.repository,
+                    pull.number,
+                    pull.user.login,
+                    !pull.spam_reasons.is_empty(),
+                    timestamp(&pull.created_at),
+                    position as i64,
+                    serde_json::to_string(pull)?
+                ],
+            )?;
+        }
+
+        for contributor in snapshot.contributors.iter() {
+            tx.execute(
+                "INSERT OR REPLACE INTO contributors
+                 (login, profile_url, avatar_url, merged_pulls, pending_pulls)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    contributor.full_name,
+                    contributor.profile_url,
+                    contributor.avatar_url,
+                    contributor.merged_pulls,
+                    contributor.pending_pulls
+                ],
+            )?;
+        }
+
+        for maintainer in maintainers.iter() {
+            tx.execute(
+                "INSERT OR REPLACE INTO maintainers
+                 (login, profile_url, avatar_url, reviews_submitted, merged_pulls,
+                  median_first_review_secs)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    maintainer.full_name,
+                    maintainer.profile_url,
+                    maintainer.avatar_url,
+                    maintainer.reviews_submitted,
+                    maintainer.merged_pulls,
+                    maintainer.median_first_review_secs
+                ],
+            )?;
+        }
+
+        tx.execute(
+            "INSERT OR REPLACE INTO snapshot_version (id, etag) VALUES (1, ?1)",
+            params![Self::content_hash(snapshot, maintainers)?],
+        )?;
+
+        let mut unannounced = Vec::with_capacity(changes.len());
+        for change in changes {
+            let announced = match change.subject() {
+                Some((kind, html_url)) => tx
+                    .query_row(
+                        "SELECT 1 FROM announced WHERE kind = ?1 AND html_url = ?2",
+                        params![kind, html_url],
+                        |_| Ok(()),
+                    )
+                    .optional()?
+                    .is_some(),
+                None => false,
+            };
+            if !announced {
+                unannounced.push(change);
+            }
+        }
+        for (kind, html_url) in snapshot.subjects() {
+            tx.execute(
+                "INSERT OR IGNORE INTO announced (kind, html_url) VALUES (?1, ?2)",
+                params![kind, html_url],
+            )?;
+        }
+
+        let finished_at = Utc::now();
+        let recorded = Self::insert_events(&tx, unannounced, finished_at)?;
+        tx.execute(
+            "INSERT INTO scrape_runs (started_at, finished_at, succeeded) VALUES (?1, ?2, 1)",
+            params![timestamp(&started_at), timestamp(&finished_at)],
+        )?;
+
+        tx.commit()?;
+        self.publish(&recorded);
+        Ok(recorded)
+    }
+
+    pub fn record_failed_scrape(
+        &self,
+        started_at: DateTime<Utc>,
+        error: &str,
+    ) -> Result<(), StoreError> {
+        let conn = self.conn.lock_recover();
+        conn.execute(
+            "INSERT INTO scrape_runs (started_at, finished_at, succeeded, error)
+             VALUES (?1, ?2, 0, ?3)",
+            params![timestamp(&started_at), timestamp(&Utc::now()), error],
+        )?;
+        Ok(())
+    }
+
+    pub fn record_events(
+        &self,
+        changes: Vec<EventKind>,
+        timestamp_: DateTime<Utc>,
+    ) -> Result<Vec<Event>, StoreError> {
+        let mut conn = self.conn.lock_recover();
+        let tx = conn.transaction()?;
+        let recorded = Self::insert_events(&tx, changes, timestamp_)?;
+        tx.commit()?;
+        self.publish(&recorded);
+        Ok(recorded)
+    }
+
+    /// Keeps `change.after` as the target with `change.key`, see `target_overrides`,
+    /// and records the change in the audit log.
+    pub fn record_target_change(
+        &self,
+        actor: &str,
+        change: AuditChange,
+        timestamp_: DateTime<Utc>,
+    ) -> Result<AuditEntry, StoreError> {
+        let mut conn = self.conn.lock_recover();
+        let tx = conn.transaction()?;
+        let target = change
+            .after
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+        tx.execute(
+            "INSERT INTO target_overrides (key, data) VALUES (?1, ?2)
+             ON CONFLICT (key) DO UPDATE SET data = excluded.data",
+            params![change.key, target],
+        )?;
+        tx.execute(
+            "INSERT INTO audit_log (timestamp, actor, data) VALUES (?1, ?2, ?3)",
+            params![
+                timestamp(&timestamp_),
+                actor,
+                serde_json::to_string(&change)?
+            ],
+        )?;
+        let id = tx.last_insert_rowid();
+        tx.commit()?;
+        Ok(AuditEntry {
+            id,
+            timestamp: timestamp_,
+            actor: actor.to_owned(),
+            change,
+        })
+    }
+
+    /// Targets changed through the admin API by key, in the order they were first
+    /// changed, `None` for deleted ones. They take precedence over the config file.
+    pub fn target_overrides(&self) -> Result<Vec<(String, Option<ScrapeTarget>)>, StoreError> {
+        let conn = self.conn.lock_recover();
+        let mut stmt = conn.prepare("SELECT key, data FROM target_overrides ORDER BY id")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+        })?;
+
+        let mut overrides = Vec::new();
+        for row in rows {
+            let (key, data) = row?;
+            let target = data.map(|data| serde_json::from_str(&data)).transpose()?;
+            overrides.push((key, target));
+        }
+        Ok(overrides)
+    }
+
+    /// The whole audit log, newest first.
+    pub fn audit_log(&self) -> Result<Vec<AuditEntry>, StoreError> {
+        let conn = self.conn.lock_recover();
+        let mut stmt =
+            conn.prepare("SELECT id, timestamp, actor, data FROM audit_log ORDER BY id DESC")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                parse_timestamp(1, row.get(1)?)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            let (id, timestamp_, actor, data) = row?;
+            entries.push(AuditEntry {
+                id,
+                timestamp: timestamp_,
+                actor,
+                change: serde_json::from_str(&data)?,
+            });
+        }
+        Ok(entries)
+    }
+
+    /// Changes with the scraped data and with the version of this build, whose
+    /// responses may be shaped differently.
+    fn content_hash(
+        snapshot: &Snapshot,
+        maintainers: &[MaintainerCollection],
+    ) -> Result<String, StoreError> {
+        let mut hasher = Sha256::new();
+        hasher.update(env!("CARGO_PKG_VERSION"));
+        hasher.update(serde_json::to_vec(&snapshot.repositories)?);
+        hasher.update(serde_json::to_vec(&snapshot.contributors)?);
+        hasher.update(serde_json::to_vec(&snapshot.pulls)?);
+        hasher.update(serde_json::to_vec(maintainers)?);
+        let hash = hasher.finalize();
+        Ok(hash[..16].iter().map(|b| format!("{b:02x}")).collect())
+    }
+
+    /// Hash of the data saved by the last successful scrape, for `ETag` headers.
+    pub fn etag(&self) -> Result<Option<String>, StoreError> {
+        let conn = self.conn.lock_recover();
+        let etag = conn
+            .query_row("SELECT etag FROM snapshot_version", [], |row| row.get(0))
+            .optional()?;
+        Ok(etag)
+    }
+
+    /// `etag` and the end of the last successful scrape, read at once so they always
+    /// belong to the same scrape.
+    pub fn version(&self) -> Result<(Option<String>, Option<DateTime<Utc>>), StoreError> {
+        let conn = self.conn.lock_recover();
+        let etag = conn
+            .query_row("SELECT etag FROM snapshot_version", [], |row| row.get(0))
+            .optional()?;
+        let finished_at = conn
+            .query_row(
+                "SELECT finished_at FROM scrape_runs WHERE succeeded = 1 ORDER BY id DESC LIMIT 1",
+                [],
+                |row| parse_timestamp(0, row.get(0)?),
+            )
+            .optional()?;
+        Ok((etag, finished_at))
+    }
+
+    pub fn last_scrape(&self, succeeded: bool) -> Result<Option<ScrapeRun>, StoreError> {
+        let conn = self.conn.lock_recover();
+        let run = conn
+            .query_row(
+                "SELECT id, started_at, finished_at, succeeded, error FROM scrape_runs
+                 WHERE succeeded = ?1 ORDER BY id DESC LIMIT 1",
+                params![succeeded],
+                |row| {
+                    Ok(ScrapeRun {
+                        id: row.get(0)?,
+                        started_at: parse_timestamp(1, row.get(1)?)?,
+                        finished_at: parse_timestamp(2, row.get(2)?)?,
+                        succeeded: row.get(3)?,
+                        error: row.get(4)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(run)
+    }
+
+    fn query_data<T: serde::de::DeserializeOwned>(
+        &self,
+        sql: &str,
+        params: impl rusqlite::Params,
+    ) -> Result<Vec<T>, StoreError> {
+        let conn = self.conn.lock_recover();
+        let mut stmt = conn.prepare(sql)?;
+        let rows = stmt.query_map(params, |row| row.get::<_, String>(0))?;
+        let mut items = Vec::new();
+        for data in rows {
+            items.push(serde_json::from_str(&data?)?);
+        }
+        Ok(items)
+    }
+
+    pub fn repositories(&self) -> Result<Vec<RepositoryCollection>, StoreError> {
+        let mut issues: HashMap<String, Vec<serde_json::Value>> = HashMap::new();
+        {
+            let conn = self.conn.lock_recover();
+            let mut stmt =
+                conn.prepare("SELECT repository, data FROM issues ORDER BY repository, position")?;
+            let rows = stmt.query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?;
+            for row in rows {
+                let (repository, data) = row?;
+                issues
+                    .entry(repository)
+                    .or_default()
+                    .push(serde_json::from_str(&data)?);
+            }
+        }
+
+        let repositories: Vec<serde_json::Value> =
+            self.query_data("SELECT data FROM repositories ORDER BY position", [])?;
+        let mut collections = Vec::with_capacity(repositories.len());
+        for mut repo in repositories {
+            let full_name = repo["full_name"].as_str().unwrap_or_default().to_owned();
+            if let Some(fields) = repo.as_object_mut() {
+                fields.insert(
+                    "issues".into(),
+                    issues.remove(&full_name).unwrap_or_default().into(),
+                );
+            }
+            collections.push(serde_json::from_value(repo)?);
+        }
+        Ok(collections)
+    }
+
+    /// One repository with its issues, `full_name` is case-insensitive.
+    pub fn repository(&self, full_name: &str) -> Result<Option<RepositoryCollection>, StoreError> {
+        let mut repo: serde_json::Value = match self
+            .query_data(
+                "SELECT data FROM repositories WHERE full_name = ?1 COLLATE NOCASE",
+                params![full_name],
+            )?
+            .pop()
+        {
+            Some(repo) => repo,
+            None => return Ok(None),
+        };
+
+        let stored_name = repo["full_name"].as_str().unwrap_or_default().to_owned();
+        let issues: Vec<serde_json::Value> = self.query_data(
+            "SELECT data FROM issues WHERE repository = ?1 ORDER BY position",
+            params![stored_name],
+        )?;
+        if let Some(fields) = repo.as_object_mut() {
+            fields.insert("issues".into(), issues.into());
+        }
+        Ok(Some(serde_json::from_value(repo)?))
+    }
+
+    pub fn pull_requests(&self) -> Result<Vec<PullRequestCollection>, StoreError> {
+        self.query_data("SELECT data FROM pull_requests ORDER BY position", [])
+    }
+
+    /// Pull requests opened by `login`, case-insensitive like GitHub logins.
+    pub fn pull_requests_by_author(
+        &self,
+        login: &str,
+    ) -> Result<Vec<PullRequestCollection>, StoreError> {
+        self.query_data(
+            "SELECT data FROM pull_requests WHERE author = ?1 COLLATE NOCASE ORDER BY position",
+            params![login],
+        )
+    }
+
+    /// One pull request, `repository` is case-insensitive.
+    pub fn pull_request(
+        &self,
+        repository: &str,
+        number: i64,
+    ) -> Result<Option<PullRequestCollection>, StoreError> {
+        Ok(self
+            .query_data(
+                "SELECT data FROM pull_requests WHERE repository = ?1 COLLATE NOCASE AND number = ?2",
+                params![repository, number],
+            )?
+            .pop())
+    }
+
+    pub fn flagged_pull_requests(&self) -> Result<Vec<PullRequestCollection>, StoreError> {
+        self.query_data(
+            "SELECT data FROM pull_requests WHERE flagged = 1 ORDER BY position",
+            [],
+        )
+    }
+
+    pub fn contributors(&self) -> Result<Vec<ContributorCollection>, StoreError> {
+        self.query_contributors(
+            "SELECT login, profile_url, avatar_url, merged_pulls, pending_pulls FROM contributors",
+            params![],
+        )
+    }
+
+    /// One contributor, `login` is case-insensitive.
+    pub fn contributor(&self, login: &str) -> Result<Option<ContributorCollection>, StoreError> {
+        Ok(self
+            .query_contributors(
+                "SELECT login, profile_url, avatar_url, merged_pulls, pending_pulls
+                 FROM contributors WHERE login = ?1 COLLATE NOCASE",
+                params![login],
+            )?
+            .pop())
+    }
+
+    fn query_contributors(
+        &self,
+        sql: &str,
+        params: impl rusqlite::Params,
+    ) -> Result<Vec<ContributorCollection>, StoreError> {
+        let conn = self.conn.lock_recover();
+        let mut stmt = conn.prepare(sql)?;
+        let rows = stmt.query_map(params, |row| {
+            Ok(ContributorCollection {
+                full_name: row.get(0)?,
+                profile_url: row.get(1)?,
+                avatar_url: row.get(2)?,
+                merged_pulls: row.get(3)?,
+                pending_pulls: row.get(4)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    pub fn maintainers(&self) -> Result<Vec<MaintainerCollection>, StoreError> {
+        let conn = self.conn.lock_recover();
+        let mut stmt = conn.prepare(
+            "SELECT login, profile_url, avatar_url, reviews_submitted, merged_pulls,
+                    median_first_review_secs
+             FROM maintainers",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(MaintainerCollection {
+                full_name: row.get(0)?,
+                profile_url: row.get(1)?,
+                avatar_url: row.get(2)?,
+                reviews_submitted: row.get(3)?,
+                merged_pulls: row.get(4)?,
+                median_first_review_secs: row.get(5)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// The last successfully scraped snapshot, `None` if there was none yet.
+    pub fn snapshot(&self) -> Result<Option<Snapshot>, StoreError> {
+        if self.last_scrape(true)?.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Snapshot {
+            repositories: self.repositories()?,
+            contributors: self.contributors()?,
+            pulls: self.pull_requests()?,
+        }))
+    }
+
+    /// Writes a consistent copy of the store to `dest.1`, keeping the `keep` most
+    /// recent copies as `dest.1` .. `dest.{keep}`.
+    pub fn backup(&self, dest: &Path, keep: usize) -> Result<(), StoreError> {
+        if keep == 0 {
+            return Ok(());
+        }
+
+        // VACUUM INTO refuses to overwrite, clean up after an interrupted backup
+        let tmp = persist::tmp_path(dest);
+        if tmp.exists() {
+            fs::remove_file(&tmp)?;
+        }
+        self.conn
+            .lock_recover()
+            .execute("VACUUM INTO ?1", params![tmp.to_string_lossy()])?;
+        persist::rotate(dest, keep)?;
+        persist::rename_durable(&tmp, &persist::numbered(dest, 1))?;
+        Ok(())
+    }
+
+    /// Imports the collections older versions cached in the config file, unless a
+    /// scrape was already stored. Returns whether anything was imported.
+    pub fn import_legacy(&self, legacy: &LegacyState) -> Result<bool, StoreError> {
+        if legacy.cached_map.is_empty() || self.last_scrape(true)?.is_some() {
+            return Ok(false);
+        }
+
+        let snapshot = Snapshot {
+            repositories: legacy_collection(&legacy.cached_map, "repo")?,
+            contributors: legacy_collection(&legacy.cached_map, "contributors")?,
+            pulls: legacy_collection(&legacy.cached_map, "pull_request")?,
+        };
+        let maintainers: Vec<MaintainerCollection> =
+            legacy_collection(&legacy.cached_map, "maintainers")?;
+        let scraped_at = legacy
+            .scrape_last
+            .map(|dt| dt.with_timezone(&Utc))
+            .unwrap_or_else(Utc::now);
+        self.save_scrape(scraped_at, &snapshot, &maintainers, vec![])?;
+        Ok(true)
+    }
+
+    /// Events recorded strictly after `since`, oldest first.
+    pub fn events(&self, since: Option<DateTime<Utc>>) -> Result<Vec<Event>, StoreError> {
+        let since = since.map(|since| timestamp(&since)).unwrap_or_default();
+        self.query_events(
+            "SELECT id, timestamp, data FROM events WHERE timestamp > ?1 ORDER BY id",
+            since,
+        )
+    }
+
+    /// Id of the most recent event, 0 when none was recorded.
+    pub fn newest_event_id(&self) -> Result<u64, StoreError> {
+        let conn = self.conn.lock_recover();
+        let id: i64 = conn.query_row("SELECT COALESCE(MAX(id), 0) FROM events", [], |row| {
+            row.get(0)
+        })?;
+        Ok(id as u64)
+    }
+
+    /// Events with an id greater than `id`, oldest first.
+    pub fn events_after(&self, id: u64) -> Result<Vec<Event>, StoreError> {
+        self.query_events(
+            "SELECT id, timestamp, data FROM events WHERE id > ?1 ORDER BY id",
+            id as i64,
+        )
+    }
+
+    fn query_events(
+        &self,
+        sql: &str,
+        param: impl rusqlite::ToSql,
+    ) -> Result<Vec<Event>, StoreError> {
+        let conn = self.conn.lock_recover();
+        let mut stmt = conn.prepare(sql)?;
+        let rows = stmt.query_map(params![param], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                parse_timestamp(1, row.get(1)?)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?;
+
+        let mut events = Vec::new();
+        for row in rows {
+            let (id, timestamp_, data) = row?;
+            events.push(Event {
+                id: id as u64,
+                timestamp: timestamp_,
+                kind: serde_json::from_str(&data)?,
+            });
+        }
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use chrono::{TimeZone, Utc};
+
+    use crate::config::LegacyState;
+    use crate::events::{diff, EventKind, Snapshot, MAX_EVENTS};
+    use crate::persist;
+    use crate::scraper::fixtures::snapshot;
+
+    use super::Store;
+
+    #[test]
+    fn test_save_scrape() -> anyhow::Result<()> {
+        let store = Store::open_in_memory()?;
+        assert!(store.snapshot()?.is_none());
+        assert!(store.etag()?.is_none());
+
+        store.save_scrape(Utc::now(), &snapshot(), &[], vec![])?;
+        store.save_scrape(Utc::now(), &snapshot(), &[], vec![])?;
+
+        let etag = store.etag()?.expect("an etag");
+        let empty = Snapshot {
+            repositories: vec![],
+            contributors: vec![],
+            pulls: vec![],
+        };
+        store.save_scrape(Utc::now(), &empty, &[], vec![])?;
+        assert_ne!(store.etag()?.expect("an etag"), etag);
+        store.save_scrape(Utc::now(), &snapshot(), &[], vec![])?;
+        assert_eq!(store.etag()?.expect("an etag"), etag);
+
+        let saved = store.snapshot()?.expect("a snapshot");
+        assert_eq!(saved.repositories.len(), 1);
+        let repo = store
+            .repository("Teknologi-Umum/PEHAPE")?
+            .expect("a repository");
+        assert_eq!(repo.issues.len(), 1);
+        assert!(store.repository("teknologi-umum/nothing")?.is_none());
+        assert!(store.pull_request("teknologi-umum/pehape", 1)?.is_some());
+        assert!(store.pull_request("teknologi-umum/pehape", 2)?.is_none());
+        assert_eq!(store.pull_requests_by_author("SOMEBODY")?.len(), 1);
+        assert!(store.contributor("SomeBody")?.is_some());
+        assert_eq!(saved.repositories[0].issues.len(), 1);
+        assert_eq!(saved.contributors.len(), 1);
+        assert!(store.last_scrape(true)?.is_some());
+        assert!(store.last_scrape(false)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_scrape_announces_once() -> anyhow::Result<()> {
+        let store = Store::open_in_memory()?;
+        let missing = || Snapshot {
+            repositories: vec![],
+            contributors: vec![],
+            pulls: vec![],
+        };
+        let scrape = |previous: &Snapshot, next: &Snapshot| -> anyhow::Result<Vec<String>> {
+            let recorded = store.save_scrape(Utc::now(), next, &[], diff(previous, next))?;
+            Ok(recorded
+                .iter()
+                .filter_map(|event| event.kind.subject())
+                .map(|(kind, _)| kind.to_owned())
+                .collect())
+        };
+
+        scrape(&missing(), &missing())?;
+        assert_eq!(
+            scrape(&missing(), &snapshot())?,
+            vec!["IssueOpened", "PullRequestOpened", "PullRequestMerged"]
+        );
+        // the repository failed to scrape, then came back
+        assert!(scrape(&snapshot(), &missing())?.is_empty());
+        assert!(scrape(&missing(), &snapshot())?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_events() -> anyhow::Result<()> {
+        let store = Store::open_in_memory()?;
+        let change = EventKind::ContributorCountChanged {
+            before: 0,
+            after: 1,
+        };
+
+        let mut subscriber = store.subscribe();
+        let first = store.record_events(vec![change.clone()], Utc.timestamp(100, 0))?;
+        assert_eq!(first[0].id, 1);
+        assert_eq!(subscriber.try_recv()?, first[0]);
+        let second = store.record_events(vec![change; MAX_EVENTS], Utc.timestamp(200, 0))?;
+        assert_eq!(second.last().unwrap().id, MAX_EVENTS as u64 + 1);
+
+        assert_eq!(store.events(None)?.len(), MAX_EVENTS);
+        assert_eq!(store.events(Some(Utc.timestamp(150, 0)))?.len(), MAX_EVENTS);
+        assert!(store.events(Some(Utc.timestamp(200, 0)))?.is_empty());
+        assert_eq!(store.events_after(MAX_EVENTS as u64)?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_legacy() -> anyhow::Result<()> {
+        let store = Store::open_in_memory()?;
+        let mut legacy = LegacyState::default();
+        legacy.cached_map.insert(
+            "contributors".to_owned(),
+            serde_json::to_string(&snapshot().contributors)?,
+        );
+
+        assert!(store.import_legacy(&legacy)?);
+        assert_eq!(store.contributors()?.len(), 1);
+        assert!(store.repositories()?.is_empty());
+        // never overwrite a real scrape
+        assert!(!store.import_legacy(&legacy)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_backup() -> anyhow::Result<()> {
+        let dir = env::temp_dir().join(format!("hacktoberfest-{}-backup", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir)?;
+        let dest = dir.join("data.db");
+
+        let store = Store::open_in_memory()?;
+        store.save_scrape(Utc::now(), &snapshot(), &[], vec![])?;
+        for _ in 0..3 {
+            store.backup(&dest, 2)?;
+        }
+
+        assert!(!persist::numbered(&dest, 3).exists());
+        let restored = Store::open(&persist::numbered(&dest, 2).to_string_lossy().into_owned())?;
+        assert_eq!(restored.contributors()?.len(), 1);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
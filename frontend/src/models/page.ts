@@ -0,0 +1,6 @@
+export type Page<T> = {
+  items: T[];
+  total: number;
+  page: number;
+  per_page: number;
+};
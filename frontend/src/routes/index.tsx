@@ -13,10 +13,15 @@ import { Label } from "~/components/label";
 import { RepositoryCard } from "~/components/repository-card";
 import { ContributorCard } from "~/components/contributor-card";
 import { Repository } from "~/models/repository";
-import { getFilteredRepositories } from "~/services/filter-repositories";
 import { getCategoriesList } from "~/services/list-categories";
 import { getContributorsList } from "~/services/list-contributors";
-import { getRepositoriesList } from "~/services/list-repositories";
+import {
+  getRepositoriesList,
+  REPOSITORIES_PER_PAGE,
+  RepositoriesQuery,
+  RepositorySort,
+} from "~/services/list-repositories";
+import { subscribeEvents } from "~/services/subscribe-events";
 import { sortIssuesByDifficulty } from "~/services/sort-issues";
 import {
   sortAndTagContributorByPRs,
@@ -24,11 +29,14 @@ import {
 } from "~/services/sort-contributors";
 import styles from "~/styles/index.css?inline";
 
+/** How long to wait for the rest of a burst of events before refetching. */
+const REFETCH_DELAY_MS = 1000;
+
 type State = {
-  activeFilters: string[];
   categories: string[];
+  query: RepositoriesQuery;
+  totalRepositories: number;
   repositories: Repository[];
-  filteredRepositories: Repository[];
   contributors: SortedContributor[];
 };
 
@@ -36,60 +44,131 @@ export default component$(() => {
   useStylesScoped$(styles);
 
   const state = useStore<State>({
-    activeFilters: [],
     categories: [],
+    query: { sort: "updated", page: 1 },
+    totalRepositories: 0,
     repositories: [],
-    filteredRepositories: [],
     contributors: [],
   });
 
-  useTask$(async () => {
-    const repositories = await getRepositoriesList();
-    const sortedByDifficulty = await sortIssuesByDifficulty(repositories);
-    state.repositories = sortedByDifficulty;
-    state.filteredRepositories = sortedByDifficulty;
-    state.categories = getCategoriesList(repositories);
+  const loadRepositories$ = $(async () => {
+    const page = await getRepositoriesList({ ...state.query });
+    state.repositories = await sortIssuesByDifficulty(page.items);
+    state.categories = getCategoriesList(page.items, state.query.topic);
+    state.totalRepositories = page.total;
+  });
 
+  useTask$(async ({ track }) => {
+    track(() => state.query);
+    await loadRepositories$();
+  });
+
+  useTask$(async () => {
     const contributors = await getContributorsList();
     const sortedByPRs = await sortAndTagContributorByPRs(contributors);
     state.contributors = sortedByPRs;
   });
 
-  useVisibleTask$(async ({ track }) => {
-    const filters = track(() => state.activeFilters);
-    const repositories = track(() => state.repositories);
-    state.filteredRepositories = await getFilteredRepositories(
-      repositories,
-      filters
-    );
+  useVisibleTask$(({ cleanup }) => {
+    // a scrape records its events in one go, refetch once per burst of them
+    let staleRepositories = false;
+    let staleContributors = false;
+    let timer: ReturnType<typeof setTimeout> | undefined;
+
+    const refetch = async () => {
+      timer = undefined;
+      if (staleRepositories) {
+        staleRepositories = false;
+        await loadRepositories$();
+      }
+      if (staleContributors) {
+        staleContributors = false;
+        const contributors = await getContributorsList();
+        state.contributors = await sortAndTagContributorByPRs(contributors);
+      }
+    };
+
+    const close = subscribeEvents((type) => {
+      if (type === "IssueOpened" || type === "reset") {
+        staleRepositories = true;
+      }
+      if (
+        type === "PullRequestOpened" ||
+        type === "PullRequestMerged" ||
+        type === "ContributorCountChanged" ||
+        type === "ContributorRankChanged" ||
+        type === "reset"
+      ) {
+        staleContributors = true;
+      }
+      if ((staleRepositories || staleContributors) && timer === undefined) {
+        timer = setTimeout(refetch, REFETCH_DELAY_MS);
+      }
+    });
+    cleanup(() => {
+      clearTimeout(timer);
+      close();
+    });
   });
 
-  const toggleFilter$ = $((filter: string) => {
-    const isFilterActive = state.activeFilters.includes(filter);
-    state.activeFilters = isFilterActive
-      ? state.activeFilters.filter((f) => f !== filter)
-      : state.activeFilters.concat(filter);
+  // a new object, so the task tracking `state.query` runs again
+  const setQuery$ = $((change: Partial<RepositoriesQuery>) => {
+    state.query = { ...state.query, page: 1, ...change };
   });
 
+  const lastPage = Math.max(
+    1,
+    Math.ceil(state.totalRepositories / REPOSITORIES_PER_PAGE)
+  );
+
+  // filtered by the backend, so the other pages are filtered too
+  const toggleTopic$ = $((topic: string) =>
+    setQuery$({ topic: state.query.topic === topic ? undefined : topic })
+  );
+
   return (
     <div>
       <Countdown />
       <Header />
+      <div class="search">
+        <input
+          type="search"
+          placeholder="Cari repository..."
+          value={state.query.q ?? ""}
+          onChange$={(_, element) => setQuery$({ q: element.value })}
+        />
+        <input
+          type="search"
+          placeholder="Bahasa, misalnya Go"
+          value={state.query.language ?? ""}
+          onChange$={(_, element) => setQuery$({ language: element.value })}
+        />
+        <select
+          value={state.query.sort}
+          onChange$={(_, element) =>
+            setQuery$({ sort: element.value as RepositorySort })
+          }
+        >
+          <option value="updated">Terbaru</option>
+          <option value="stars">Bintang terbanyak</option>
+          <option value="issues">Issue terbanyak</option>
+        </select>
+      </div>
       <p class="filter-tips">
         Kesusahan nyari issue? Klik aja filter di bawah biar gampang nyarinya!
       </p>
       <div class="filters">
         {state.categories.map((category) => {
-          const isFilterActive = state.activeFilters.includes(category);
+          const isFilterActive = state.query.topic === category;
           return (
-            <div key={category} onClick$={() => toggleFilter$(category)}>
+            <div key={category} onClick$={() => toggleTopic$(category)}>
               <Label text={category} isGlowing={isFilterActive} />
             </div>
           );
         })}
       </div>
       <div class="repository-card-container">
-        {state.filteredRepositories.map((repo) => (
+        {state.repositories.map((repo) => (
           <RepositoryCard
             key={repo.html_url}
             full_name={repo.full_name}
@@ -100,6 +179,23 @@ export default component$(() => {
           />
         ))}
       </div>
+      <div class="pagination">
+        <button
+          disabled={state.query.page <= 1}
+          onClick$={() => setQuery$({ page: state.query.page - 1 })}
+        >
+          Sebelumnya
+        </button>
+        <span>
+          {state.query.page} / {lastPage}
+        </span>
+        <button
+          disabled={state.query.page >= lastPage}
+          onClick$={() => setQuery$({ page: state.query.page + 1 })}
+        >
+          Berikutnya
+        </button>
+      </div>
 
       <p class="contributor-section-title">Top Contributors</p>
       <div class="contributor-card-container">
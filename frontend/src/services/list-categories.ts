@@ -1,17 +1,23 @@
 import type { Repository } from "~/models/repository";
 
-export function getCategoriesList(repositories: Repository[]): string[] {
-  const uniqueLabels = new Set<string>();
+/**
+ * The topics of `repositories`, plus the `selected` one so it can be cleared
+ * even when the current page doesn't have it.
+ */
+export function getCategoriesList(
+  repositories: Repository[],
+  selected?: string
+): string[] {
+  const uniqueTopics = new Set<string>();
+  if (selected) uniqueTopics.add(selected);
 
   for (const repository of repositories) {
-    for (const issue of repository.issues) {
-      for (const label of issue.labels) {
-        // ignore hacktoberfest label since they all should have it
-        if (label.name === "hacktoberfest") continue;
-        uniqueLabels.add(label.name);
-      }
+    for (const topic of repository.topics) {
+      // ignore hacktoberfest topic since they all should have it
+      if (topic === "hacktoberfest") continue;
+      uniqueTopics.add(topic);
     }
   }
 
-  return [...uniqueLabels];
+  return [...uniqueTopics];
 }
@@ -3,7 +3,7 @@ import { API_BASE_URL } from "~/env";
 import { Contributor } from "~/models/contributor";
 
 export const getContributorsList = $(async () => {
-  const url = new URL("/contrib", API_BASE_URL);
+  const url = new URL("/v1/contrib", API_BASE_URL);
   const response = await fetch(url);
   const contributors: Contributor[] = await response.json();
 
@@ -1,9 +1,38 @@
 import { $ } from "@builder.io/qwik";
 import { API_BASE_URL } from "~/env";
+import type { Page } from "~/models/page";
 import type { Repository } from "~/models/repository";
 
-export const getRepositoriesList = $(async (): Promise<Repository[]> => {
-  const url = new URL("/repo", API_BASE_URL);
-  const response = await fetch(url);
-  return response.json();
-});
+export type RepositorySort = "updated" | "stars" | "issues";
+
+export type RepositoriesQuery = {
+  language?: string;
+  topic?: string;
+  q?: string;
+  sort: RepositorySort;
+  page: number;
+};
+
+export const REPOSITORIES_PER_PAGE = 30;
+
+/**
+ * One page of the repositories matching `query`, filtered and sorted by the
+ * backend.
+ */
+export const getRepositoriesList = $(
+  async (query: RepositoriesQuery): Promise<Page<Repository>> => {
+    const url = new URL("/v1/repo", API_BASE_URL);
+    if (query.language) url.searchParams.set("language", query.language);
+    if (query.topic) url.searchParams.set("topic", query.topic);
+    if (query.q) url.searchParams.set("q", query.q);
+    url.searchParams.set("sort", query.sort);
+    url.searchParams.set("page", String(query.page));
+    url.searchParams.set("per_page", String(REPOSITORIES_PER_PAGE));
+
+    const response = await fetch(url);
+    if (!response.ok) {
+      throw new Error(`fetching ${url} failed with ${response.status}`);
+    }
+    return response.json();
+  }
+);
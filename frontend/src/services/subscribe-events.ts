@@ -0,0 +1,35 @@
+import { API_BASE_URL } from "~/env";
+
+export type EventType =
+  | "IssueOpened"
+  | "PullRequestOpened"
+  | "PullRequestMerged"
+  | "ContributorCountChanged"
+  | "ContributorRankChanged"
+  | "GoalReached"
+  | "ScrapeFailing";
+
+/**
+ * Calls `onEvent` with the type of every event the backend records, and with
+ * "reset" when it missed more than it can replay. The browser reconnects and
+ * resumes on its own. Returns a function that closes the stream.
+ */
+export function subscribeEvents(
+  onEvent: (type: EventType | "reset") => void
+): () => void {
+  const source = new EventSource(new URL("/v1/stream", API_BASE_URL));
+  const types: (EventType | "reset")[] = [
+    "IssueOpened",
+    "PullRequestOpened",
+    "PullRequestMerged",
+    "ContributorCountChanged",
+    "ContributorRankChanged",
+    "GoalReached",
+    "ScrapeFailing",
+    "reset",
+  ];
+  for (const type of types) {
+    source.addEventListener(type, () => onEvent(type));
+  }
+  return () => source.close();
+}
@@ -2,32 +2,26 @@ import { expect, it } from "vitest";
 import { getCategoriesList } from "~/services/list-categories";
 import { sampleRepositories } from "./repositories.fixture";
 
-it("should get labels from the repositories", () => {
+it("should get topics from the repositories", () => {
   const categories = getCategoriesList(sampleRepositories);
 
-  expect(categories).toContain("scope: frontend");
-  expect(categories).toContain("difficulty: easy");
-  expect(categories).toContain("difficulty: medium");
-  expect(categories).toContain("enhancement");
-  expect(categories).toContain("good first issue");
-  expect(categories).toContain("help wanted");
+  expect(categories).toContain("rce");
+  expect(categories).toContain("remote-code-execution-engine");
 });
 
 it("should not contain duplicate entries", () => {
-  const categories = getCategoriesList(sampleRepositories);
+  const categories = getCategoriesList(sampleRepositories, "rce");
+
+  expect(categories.filter((category) => category === "rce")).toHaveLength(1);
+});
+
+it("should keep the selected topic", () => {
+  const categories = getCategoriesList([], "golang");
 
-  expect(
-    categories.filter((category) => category === "difficulty: easy")
-  ).toHaveLength(1);
-  expect(
-    categories.filter((category) => category === "good first issue")
-  ).toHaveLength(1);
-  expect(
-    categories.filter((category) => category === "help wanted")
-  ).toHaveLength(1);
+  expect(categories).toEqual(["golang"]);
 });
 
-it('should exclude "hacktoberfest" labels', () => {
+it('should exclude "hacktoberfest" topics', () => {
   const categories = getCategoriesList(sampleRepositories);
 
   expect(categories).not.toContain("hacktoberfest");